@@ -15,8 +15,15 @@ pub trait Action: Sync {
     fn long_timeout(&self) -> f32 {
         0.0
     }
+    // false for actions with `DisableAutomaticStates` in the manifest, whose keys
+    // only change state through `set_state`
+    fn automatic_states(&self) -> bool {
+        true
+    }
     async fn on_appear(&self, e: AppearEvent, sd: StreamDeck) {}
     async fn on_disappear(&self, e: AppearEvent, sd: StreamDeck) {}
+    // called after `on_disappear`, once the SDK dropped everything it kept for the context
+    async fn on_instance_removed(&self, context: String, sd: StreamDeck) {}
     async fn on_key_down(&self, e: KeyEvent, sd: StreamDeck) {}
     async fn on_key_up(&self, e: KeyEvent, sd: StreamDeck) {}
     // custom actions
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use crate::Action;
 
+#[derive(Default)]
 pub struct ActionManager {
     pub(crate) actions: HashMap<String, Box<dyn Action + Send>>,
 }
@@ -20,11 +21,11 @@ impl ActionManager {
         self
     }
 
-    pub(crate) fn get(&self, uuid: &String) -> &Box<dyn Action + Send> {
-        self.actions.get(uuid).unwrap()
+    pub(crate) fn get(&self, uuid: &str) -> &(dyn Action + Send) {
+        self.actions.get(uuid).unwrap().as_ref()
     }
 
-    pub(crate) fn shared(&self) -> Option<&Box<dyn Action + Send>> {
-        self.actions.get(&"shared".to_string())
+    pub(crate) fn shared(&self) -> Option<&(dyn Action + Send)> {
+        self.actions.get("shared").map(|action| action.as_ref())
     }
 }
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::Arc;
+use std::time::Duration;
+
+use image::codecs::gif::GifDecoder;
+use image::{AnimationDecoder, DynamicImage};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::images::{resize_image, ImageError, ResizeMode, DEFAULT_MAX_BYTES};
+use crate::stream_deck::StreamDeck;
+
+/// Frames per second sent for all the animated keys together, unless changed
+/// with [`StreamDeck::set_animation_fps`].
+pub const DEFAULT_MAX_FPS: u32 = 30;
+
+// shortest frame delay, most browsers apply the same limit to GIFs
+const MIN_DELAY: Duration = Duration::from_millis(20);
+
+/// Pre-encoded frames (data URIs) with the time each one stays on the key.
+#[derive(Clone)]
+pub struct Animation {
+    frames: Arc<Vec<(String, Duration)>>,
+}
+
+impl Animation {
+    /// Decode every frame of a GIF, resized to `size`x`size` and encoded once.
+    pub fn from_gif(gif: &[u8], size: u32) -> Result<Self, ImageError> {
+        let decoder =
+            GifDecoder::new(Cursor::new(gif)).map_err(|e| ImageError::Decode(e.to_string()))?;
+        let frames = decoder
+            .into_frames()
+            .collect_frames()
+            .map_err(|e| ImageError::Decode(e.to_string()))?;
+        let mut encoded = Vec::with_capacity(frames.len());
+        for frame in frames {
+            let (numerator, denominator) = frame.delay().numer_denom_ms();
+            let delay = Duration::from_millis((numerator / denominator.max(1)) as u64);
+            let image = DynamicImage::ImageRgba8(frame.into_buffer());
+            let png = crate::images::encode_png(&image)?;
+            let data = resize_image(&png, size, ResizeMode::Fit, DEFAULT_MAX_BYTES)?;
+            encoded.push((data, delay));
+        }
+        Ok(Animation::from_frames(encoded))
+    }
+
+    /// Frames already encoded as data URIs, each with its delay.
+    pub fn from_frames(frames: Vec<(String, Duration)>) -> Self {
+        let frames = frames
+            .into_iter()
+            .map(|(data, delay)| {
+                let delay = if delay.is_zero() {
+                    Duration::from_millis(100)
+                } else {
+                    delay.max(MIN_DELAY)
+                };
+                (data, delay)
+            })
+            .collect();
+        Animation {
+            frames: Arc::new(frames),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+struct Playback {
+    animation: Animation,
+    looping: bool,
+    // next frame to send
+    frame: usize,
+    task: Option<JoinHandle<()>>,
+}
+
+#[derive(Clone)]
+pub struct Animator {
+    playbacks: Arc<Mutex<HashMap<String, Playback>>>,
+    // minimum interval between two frames of any key, next time a frame can be sent
+    limiter: Arc<Mutex<(Duration, Instant)>>,
+}
+
+impl Default for Animator {
+    fn default() -> Self {
+        Animator {
+            playbacks: Arc::new(Mutex::new(HashMap::new())),
+            limiter: Arc::new(Mutex::new((
+                Duration::from_secs(1) / DEFAULT_MAX_FPS,
+                Instant::now(),
+            ))),
+        }
+    }
+}
+
+impl Animator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn is_playing(&self, context: &str) -> bool {
+        match self.playbacks.lock().await.get(context) {
+            Some(playback) => playback.task.is_some(),
+            None => false,
+        }
+    }
+
+    // wait for the next free slot of the global frame rate
+    async fn slot(&self) {
+        let mut limiter = self.limiter.lock().await;
+        let (interval, next) = *limiter;
+        let now = Instant::now();
+        let at = next.max(now);
+        limiter.1 = at + interval;
+        drop(limiter);
+        tokio::time::sleep_until(at).await;
+    }
+}
+
+impl StreamDeck {
+    /// Play `animation` on `context`, once or in a loop.
+    ///
+    /// A looping animation pauses when the context disappears and resumes from
+    /// the same frame when it appears again, until [`StreamDeck::stop_animation`].
+    /// A one-shot animation stops when the context disappears.
+    pub async fn play_animation(&self, context: String, animation: Animation, looping: bool) {
+        if animation.is_empty() {
+            return;
+        }
+        let mut playbacks = self.animator.playbacks.lock().await;
+        if let Some(task) = playbacks.remove(&context).and_then(|p| p.task) {
+            task.abort();
+        }
+        let visible = self.is_visible(&context).await;
+        let task = if visible {
+            Some(tokio::spawn(self.clone().animate(context.clone())))
+        } else {
+            None
+        };
+        playbacks.insert(
+            context,
+            Playback {
+                animation,
+                looping,
+                frame: 0,
+                task,
+            },
+        );
+    }
+
+    /// Stop the animation of `context`, the key keeps the current frame.
+    pub async fn stop_animation(&self, context: &str) {
+        let playback = self.animator.playbacks.lock().await.remove(context);
+        if let Some(task) = playback.and_then(|p| p.task) {
+            task.abort();
+        }
+    }
+
+    /// Limit the frames per second sent for all the animated keys together.
+    pub async fn set_animation_fps(&self, max_fps: u32) {
+        self.animator.limiter.lock().await.0 = Duration::from_secs(1) / max_fps.max(1);
+    }
+
+    // one-shot animations are dropped, they would resume mid-way anyway
+    pub(crate) async fn pause_animation(&self, context: &str) {
+        let mut playbacks = self.animator.playbacks.lock().await;
+        let looping = match playbacks.get_mut(context) {
+            Some(playback) => {
+                if let Some(task) = playback.task.take() {
+                    task.abort();
+                }
+                playback.looping
+            }
+            None => return,
+        };
+        if !looping {
+            playbacks.remove(context);
+        }
+    }
+
+    pub(crate) async fn resume_animation(&self, context: &str) {
+        if let Some(playback) = self.animator.playbacks.lock().await.get_mut(context) {
+            if playback.task.is_none() {
+                playback.task = Some(tokio::spawn(self.clone().animate(context.to_string())));
+            }
+        }
+    }
+
+    async fn animate(self, context: String) {
+        loop {
+            let mut playbacks = self.animator.playbacks.lock().await;
+            let playback = match playbacks.get_mut(&context) {
+                Some(playback) => playback,
+                None => return,
+            };
+            if playback.frame >= playback.animation.len() {
+                if !playback.looping {
+                    playbacks.remove(&context);
+                    return;
+                }
+                playback.frame = 0;
+            }
+            let (data, delay) = playback.animation.frames[playback.frame].clone();
+            playback.frame += 1;
+            drop(playbacks);
+
+            self.animator.slot().await;
+            self.set_image_b64(context.clone(), Some(data)).await;
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
@@ -3,16 +3,37 @@ use std::env;
 use serde::Deserialize;
 use serde_repr::Deserialize_repr;
 
-#[derive(Deserialize_repr, Clone)]
+#[derive(Deserialize_repr, Clone, Copy, PartialEq, Eq, Debug)]
 #[repr(u16)]
 pub enum DeviceType {
     StreamDeck = 0,
     StreamDeckMini = 1,
     StreamDeckXL = 2,
     StreamDeckMobile = 3,
+    CorsairGKeys = 4,
     StreamDeckPedal = 5,
     CorsairVoyager = 6,
     StreamDeckPlus = 7,
+    ScufController = 8,
+    StreamDeckNeo = 9,
+}
+
+impl DeviceType {
+    pub fn from_raw(value: i32) -> Option<DeviceType> {
+        match value {
+            0 => Some(DeviceType::StreamDeck),
+            1 => Some(DeviceType::StreamDeckMini),
+            2 => Some(DeviceType::StreamDeckXL),
+            3 => Some(DeviceType::StreamDeckMobile),
+            4 => Some(DeviceType::CorsairGKeys),
+            5 => Some(DeviceType::StreamDeckPedal),
+            6 => Some(DeviceType::CorsairVoyager),
+            7 => Some(DeviceType::StreamDeckPlus),
+            8 => Some(DeviceType::ScufController),
+            9 => Some(DeviceType::StreamDeckNeo),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Deserialize, Clone)]
@@ -34,6 +55,8 @@ pub struct ApplicationInfo {
     pub version: Option<String>,
 }
 
+// not read from `Info` yet
+#[allow(dead_code)]
 #[derive(Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ColorsInfo {
@@ -50,7 +73,7 @@ pub struct PluginInfo {
     pub version: Option<String>,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
 pub struct DeviceSize {
     pub columns: i32,
     pub rows: i32,
@@ -85,17 +108,16 @@ pub fn parse_args() -> StreamDeckArgs {
         "-pluginUUID" => plugin_uuid = args[i + 1].to_string(),
         "-registerEvent" => register_event = args[i + 1].to_string(),
         "-info" => {
-            let parsed = serde_json::from_str(&args[i + 1].to_string());
-            if parsed.is_ok() {
-                info = parsed.unwrap();
+            if let Ok(parsed) = serde_json::from_str(&args[i + 1]) {
+                info = parsed;
             }
         }
         &_ => {}
     });
-    return StreamDeckArgs {
+    StreamDeckArgs {
         port,
         plugin_uuid,
         register_event,
         info,
-    };
+    }
 }
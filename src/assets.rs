@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tokio::sync::Mutex;
+
+use crate::dedupe::Update;
+use crate::events::events::ActionState;
+use crate::events::sent::set_image;
+use crate::images::image_to_data_uri;
+use crate::outbox::update_key;
+use crate::stream_deck::StreamDeck;
+
+// extensions tried for paths without one, like the images of the manifest
+const EXTENSIONS: [&str; 2] = ["png", "svg"];
+
+struct Asset {
+    modified: Option<SystemTime>,
+    data: String,
+}
+
+/// Images shipped in the plugin folder, loaded and encoded as data URIs once.
+#[derive(Clone)]
+pub struct Assets {
+    root: PathBuf,
+    cache: Arc<Mutex<HashMap<PathBuf, Asset>>>,
+    watch: Arc<Mutex<bool>>,
+}
+
+impl Assets {
+    /// Assets resolved relative to `root`.
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Assets {
+            root: root.as_ref().to_path_buf(),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+            watch: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    /// Assets of the `.sdPlugin` folder holding the plugin executable, which may
+    /// sit in a subfolder (e.g. `bin/`). Falls back to the current directory,
+    /// where the Stream Deck application starts plugins.
+    pub fn of_plugin() -> Self {
+        let root = std::env::current_exe()
+            .ok()
+            .and_then(|exe| {
+                exe.ancestors()
+                    .find(|dir| {
+                        dir.file_name()
+                            .and_then(|name| name.to_str())
+                            .is_some_and(|name| name.ends_with(".sdPlugin"))
+                    })
+                    .map(Path::to_path_buf)
+            })
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."));
+        Assets::new(root)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Reload files changed on disk since they were cached, handy while
+    /// developing. Every load checks the modification time when enabled.
+    pub async fn watch(&self, enabled: bool) {
+        *self.watch.lock().await = enabled;
+    }
+
+    /// `path` relative to the plugin folder, trying `.png` and `.svg` when it
+    /// has no extension.
+    pub fn resolve<P: AsRef<Path>>(&self, path: P) -> Option<PathBuf> {
+        let path = self.root.join(path);
+        if path.extension().is_some() {
+            return path.is_file().then_some(path);
+        }
+        EXTENSIONS
+            .iter()
+            .map(|extension| path.with_extension(extension))
+            .find(|path| path.is_file())
+    }
+
+    /// The image at `path` as a data URI.
+    pub async fn load<P: AsRef<Path>>(&self, path: P) -> io::Result<String> {
+        let path = self.resolve(&path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("asset {} not found", path.as_ref().display()),
+            )
+        })?;
+        let watch = *self.watch.lock().await;
+        let modified = if watch {
+            std::fs::metadata(&path)?.modified().ok()
+        } else {
+            None
+        };
+        if let Some(asset) = self.cache.lock().await.get(&path) {
+            if !watch || asset.modified == modified {
+                return Ok(asset.data.clone());
+            }
+        }
+        let bytes = tokio::fs::read(&path).await?;
+        let data = image_to_data_uri(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        self.cache.lock().await.insert(
+            path,
+            Asset {
+                modified,
+                data: data.clone(),
+            },
+        );
+        Ok(data)
+    }
+
+    /// Like [`Assets::load`], picking the `@2x` variant (`icon@2x.png` for
+    /// `icon.png`) when it exists and the key is at least 144 pixels wide.
+    pub async fn load_for_size<P: AsRef<Path>>(&self, path: P, size: u32) -> io::Result<String> {
+        match self.variant_for_size(path.as_ref(), size) {
+            Some(variant) => self.load(variant).await,
+            None => self.load(path).await,
+        }
+    }
+
+    /// Like [`Assets::resolve`], picking the `@2x` variant like [`Assets::load_for_size`].
+    pub fn resolve_for_size<P: AsRef<Path>>(&self, path: P, size: u32) -> Option<PathBuf> {
+        match self.variant_for_size(path.as_ref(), size) {
+            Some(variant) => self.resolve(variant),
+            None => self.resolve(path),
+        }
+    }
+
+    fn variant_for_size(&self, path: &Path, size: u32) -> Option<PathBuf> {
+        if size < 144 {
+            return None;
+        }
+        high_resolution(path).filter(|variant| self.resolve(variant).is_some())
+    }
+
+    /// Forget the cached files.
+    pub async fn clear(&self) {
+        self.cache.lock().await.clear();
+    }
+}
+
+fn high_resolution(path: &Path) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+    let mut name = format!("{stem}@2x");
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        name = format!("{name}.{extension}");
+    }
+    Some(path.with_file_name(name))
+}
+
+impl StreamDeck {
+    /// Send the image at `path` in the plugin folder, in the `@2x` variant on
+    /// high resolution devices, for one `state` only or, if `None`, for every state.
+    pub async fn set_image_asset<P: AsRef<Path>>(
+        &self,
+        context: String,
+        path: P,
+        state: Option<ActionState>,
+    ) -> io::Result<()> {
+        let size = self.key_image_size(&context).await;
+        let data = Some(self.assets.load_for_size(path, size).await?);
+        if !self
+            .sent_updates
+            .is_new(&context, Update::Image, None, state.as_ref(), &data)
+            .await
+        {
+            return Ok(());
+        }
+        let key = update_key("setImage", &context, None, state.as_ref());
+        self.send_update(key, set_image(context, data, None, state))
+            .await;
+        Ok(())
+    }
+}
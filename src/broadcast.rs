@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use serde::de::value::MapDeserializer;
+use serde_json::Value;
+
+use crate::instances::InstanceInfo;
+use crate::key_update::KeyUpdate;
+use crate::stream_deck::StreamDeck;
+
+type SettingsPredicate = Box<dyn Fn(&HashMap<String, Value>) -> bool + Send + Sync>;
+
+/// Contexts a broadcast is sent to.
+pub enum Audience {
+    /// Every instance of an action UUID.
+    Action(String),
+    /// Every instance on a device.
+    Device(String),
+    /// Every instance whose settings match a predicate.
+    Settings(SettingsPredicate),
+}
+
+impl Audience {
+    /// Instances whose settings deserialize to `T` and match `predicate`.
+    pub fn settings<T, F>(predicate: F) -> Self
+    where
+        T: serde::de::DeserializeOwned,
+        F: Fn(&T) -> bool + Send + Sync + 'static,
+    {
+        Audience::Settings(Box::new(move |settings| {
+            T::deserialize(MapDeserializer::new(settings.clone().into_iter()))
+                .is_ok_and(|settings| predicate(&settings))
+        }))
+    }
+}
+
+impl StreamDeck {
+    /// Visible contexts in `audience`.
+    pub async fn contexts_in(&self, audience: &Audience) -> Vec<String> {
+        match audience {
+            Audience::Action(uuid) => self.contexts_of(uuid).await,
+            Audience::Device(device) => self
+                .instances
+                .all()
+                .await
+                .into_iter()
+                .filter(|instance| instance.device == *device)
+                .map(|instance| instance.context)
+                .collect(),
+            Audience::Settings(predicate) => self
+                .instances_settings
+                .lock()
+                .await
+                .iter()
+                .filter(|(_, settings)| predicate(settings))
+                .map(|(context, _)| context.clone())
+                .collect(),
+        }
+    }
+
+    /// Apply the same update to every context in `audience`.
+    pub async fn broadcast(&self, audience: &Audience, update: KeyUpdate) {
+        for context in self.contexts_in(audience).await {
+            self.apply_key_update(context, update.clone()).await;
+        }
+    }
+
+    /// Apply the update computed for each instance in `audience`, `None` leaves it unchanged.
+    pub async fn broadcast_with<F>(&self, audience: &Audience, update: F)
+    where
+        F: Fn(&InstanceInfo) -> Option<KeyUpdate>,
+    {
+        for context in self.contexts_in(audience).await {
+            let instance = match self.instances.get(&context).await {
+                Some(instance) => instance,
+                None => continue,
+            };
+            if let Some(update) = update(&instance) {
+                self.apply_key_update(context, update).await;
+            }
+        }
+    }
+
+    pub async fn broadcast_title(&self, audience: &Audience, title: Option<String>) {
+        for context in self.contexts_in(audience).await {
+            self.set_title(context, title.clone()).await;
+        }
+    }
+
+    pub async fn broadcast_image_b64(&self, audience: &Audience, image: Option<String>) {
+        for context in self.contexts_in(audience).await {
+            self.set_image_b64(context, image.clone()).await;
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::action::Action;
+use crate::events::events::{
+    AppearEvent, ApplicationEvent, DeviceDidConnectEvent, DeviceDidDisconnectEvent, DialPressEvent,
+    DialRotateEvent, DidReceiveGlobalSettingsEvent, DidReceiveSettingsEvent, KeyEvent,
+    PropertyInspectorAppearEvent, SendToPluginEvent, SystemDidWakeUpEvent,
+    TitleParametersDidChangeEvent, TouchTapEvent,
+};
+use crate::stream_deck::StreamDeck;
+
+/// Title shown while a [`Confirm`] key waits for the confirmation.
+pub const DEFAULT_PROMPT: &str = "Sure?";
+
+// steps of the progress shown while holding
+const HOLD_STEPS: u32 = 5;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConfirmMode {
+    /// The first press arms the key, a second press within the window confirms.
+    PressTwice { window: Duration },
+    /// Keeping the key down for the duration confirms.
+    Hold { duration: Duration },
+}
+
+struct Guard {
+    id: u64,
+    // title to restore once confirmed or cancelled
+    title: Option<String>,
+    // the hold confirmed, the key up that follows is not forwarded
+    confirmed: bool,
+}
+
+#[derive(Clone, Default)]
+struct Guards {
+    guards: Arc<Mutex<HashMap<String, Guard>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl Guards {
+    async fn arm(&self, sd: &StreamDeck, context: &str) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let title = sd
+            .instance(context)
+            .await
+            .and_then(|instance| instance.title);
+        self.guards.lock().await.insert(
+            context.to_string(),
+            Guard {
+                id,
+                title,
+                confirmed: false,
+            },
+        );
+        id
+    }
+
+    // disarm `context` (only if still armed by `id`) and restore its title
+    async fn reset(&self, sd: &StreamDeck, context: &str, id: Option<u64>) -> Option<Guard> {
+        let mut guards = self.guards.lock().await;
+        if id.is_some() && guards.get(context).map(|guard| guard.id) != id {
+            return None;
+        }
+        let guard = guards.remove(context)?;
+        drop(guards);
+        if sd.is_visible(context).await {
+            sd.set_title(context.to_string(), guard.title.clone()).await;
+        }
+        Some(guard)
+    }
+}
+
+/// Wrap a destructive action so its `on_key_up` only runs once confirmed,
+/// by pressing twice or by holding the key. The other events are forwarded
+/// as they are, except the long press in hold mode, which the hold replaces.
+pub struct Confirm<A> {
+    action: Arc<A>,
+    mode: ConfirmMode,
+    prompt: String,
+    guards: Guards,
+}
+
+impl<A: Action + Send + 'static> Confirm<A> {
+    pub fn new(action: A, mode: ConfirmMode) -> Self {
+        Confirm {
+            action: Arc::new(action),
+            mode,
+            prompt: DEFAULT_PROMPT.to_string(),
+            guards: Guards::default(),
+        }
+    }
+
+    pub fn press_twice(action: A, window: Duration) -> Self {
+        Confirm::new(action, ConfirmMode::PressTwice { window })
+    }
+
+    pub fn hold(action: A, duration: Duration) -> Self {
+        Confirm::new(action, ConfirmMode::Hold { duration })
+    }
+
+    pub fn prompt(mut self, prompt: &str) -> Self {
+        self.prompt = prompt.to_string();
+        self
+    }
+
+    // runs beside the key events, so the key up can cancel it
+    async fn hold_key(&self, e: KeyEvent, duration: Duration, sd: StreamDeck) {
+        let id = self.guards.arm(&sd, &e.context).await;
+        let action = self.action.clone();
+        let guards = self.guards.clone();
+        let prompt = self.prompt.clone();
+        tokio::spawn(async move {
+            let start = Instant::now();
+            let mut interval = tokio::time::interval(duration / HOLD_STEPS);
+            for step in 0..HOLD_STEPS {
+                interval.tick().await;
+                // checked and shown under the lock, so a release restoring the
+                // title in between cannot be overwritten by the progress
+                let held = guards.guards.lock().await;
+                // released or gone before the end
+                if held.get(&e.context).map(|guard| guard.id) != Some(id) {
+                    return;
+                }
+                let bar: String = (0..HOLD_STEPS)
+                    .map(|i| if i <= step { '■' } else { '□' })
+                    .collect();
+                sd.set_title(e.context.clone(), Some(format!("{prompt}\n{bar}")))
+                    .await;
+                drop(held);
+            }
+            tokio::time::sleep_until(start + duration).await;
+            let mut held = guards.guards.lock().await;
+            match held.get_mut(&e.context) {
+                Some(guard) if guard.id == id => guard.confirmed = true,
+                _ => return,
+            }
+            drop(held);
+            action.on_key_up(e, sd).await;
+        });
+    }
+}
+
+#[async_trait]
+impl<A: Action + Send + 'static> Action for Confirm<A> {
+    fn uuid(&self) -> &str {
+        self.action.uuid()
+    }
+    fn automatic_states(&self) -> bool {
+        self.action.automatic_states()
+    }
+    // the hold is the long press, its key up must not be skipped
+    fn long_timeout(&self) -> f32 {
+        match self.mode {
+            ConfirmMode::PressTwice { .. } => self.action.long_timeout(),
+            ConfirmMode::Hold { .. } => 0.0,
+        }
+    }
+    async fn on_appear(&self, e: AppearEvent, sd: StreamDeck) {
+        self.action.on_appear(e, sd).await;
+    }
+    async fn on_disappear(&self, e: AppearEvent, sd: StreamDeck) {
+        self.guards.guards.lock().await.remove(&e.context);
+        self.action.on_disappear(e, sd).await;
+    }
+    async fn on_instance_removed(&self, context: String, sd: StreamDeck) {
+        self.action.on_instance_removed(context, sd).await;
+    }
+    async fn on_key_down(&self, e: KeyEvent, sd: StreamDeck) {
+        self.action.on_key_down(e.clone(), sd.clone()).await;
+        if let ConfirmMode::Hold { duration } = self.mode {
+            self.hold_key(e, duration, sd).await;
+        }
+    }
+    async fn on_key_up(&self, e: KeyEvent, sd: StreamDeck) {
+        match self.mode {
+            ConfirmMode::PressTwice { window } => {
+                if self.guards.reset(&sd, &e.context, None).await.is_some() {
+                    self.action.on_key_up(e, sd).await;
+                    return;
+                }
+                let id = self.guards.arm(&sd, &e.context).await;
+                sd.set_title(e.context.clone(), Some(self.prompt.clone()))
+                    .await;
+                let guards = self.guards.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(window).await;
+                    guards.reset(&sd, &e.context, Some(id)).await;
+                });
+            }
+            ConfirmMode::Hold { .. } => {
+                // confirmed holds already ran the action, early releases cancel
+                if let Some(guard) = self.guards.reset(&sd, &e.context, None).await {
+                    if !guard.confirmed {
+                        sd.show_alert(e.context).await;
+                    }
+                }
+            }
+        }
+    }
+    async fn on_long_press(&self, e: KeyEvent, timeout: f32, sd: StreamDeck) {
+        self.action.on_long_press(e, timeout, sd).await;
+    }
+    async fn on_settings_changed(&self, e: DidReceiveSettingsEvent, sd: StreamDeck) {
+        self.action.on_settings_changed(e, sd).await;
+    }
+    async fn on_global_settings_changed(&self, e: DidReceiveGlobalSettingsEvent, sd: StreamDeck) {
+        self.action.on_global_settings_changed(e, sd).await;
+    }
+    async fn on_dial_rotate(&self, e: DialRotateEvent, sd: StreamDeck) {
+        self.action.on_dial_rotate(e, sd).await;
+    }
+    async fn on_dial_press(&self, e: DialPressEvent, sd: StreamDeck) {
+        self.action.on_dial_press(e, sd).await;
+    }
+    async fn on_touch_tap(&self, e: TouchTapEvent, sd: StreamDeck) {
+        self.action.on_touch_tap(e, sd).await;
+    }
+    async fn on_title_parameters_changed(&self, e: TitleParametersDidChangeEvent, sd: StreamDeck) {
+        self.action.on_title_parameters_changed(e, sd).await;
+    }
+    async fn on_device_connect(&self, e: DeviceDidConnectEvent, sd: StreamDeck) {
+        self.action.on_device_connect(e, sd).await;
+    }
+    async fn on_device_disconnect(&self, e: DeviceDidDisconnectEvent, sd: StreamDeck) {
+        self.action.on_device_disconnect(e, sd).await;
+    }
+    async fn on_application_launch(&self, e: ApplicationEvent, sd: StreamDeck) {
+        self.action.on_application_launch(e, sd).await;
+    }
+    async fn on_application_terminate(&self, e: ApplicationEvent, sd: StreamDeck) {
+        self.action.on_application_terminate(e, sd).await;
+    }
+    async fn on_system_wake_up(&self, e: SystemDidWakeUpEvent, sd: StreamDeck) {
+        self.action.on_system_wake_up(e, sd).await;
+    }
+    async fn on_property_inspector_appear(&self, e: PropertyInspectorAppearEvent, sd: StreamDeck) {
+        self.action.on_property_inspector_appear(e, sd).await;
+    }
+    async fn on_property_inspector_disappear(
+        &self,
+        e: PropertyInspectorAppearEvent,
+        sd: StreamDeck,
+    ) {
+        self.action.on_property_inspector_disappear(e, sd).await;
+    }
+    async fn on_send_to_plugin(&self, e: SendToPluginEvent, sd: StreamDeck) {
+        self.action.on_send_to_plugin(e, sd).await;
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::events::events::{
+    ActionState, KeyEvent, StreamDeckTarget, TitleParametersDidChangeEvent,
+};
+use crate::stream_deck::StreamDeck;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub(crate) enum Update {
+    Title,
+    Image,
+    State,
+    /// One key of the `setFeedback` payload.
+    Feedback(String),
+}
+
+// what was updated, on which target and for which state
+type UpdateKey = (Update, Option<u16>, Option<u16>);
+
+/// Hash of the last value sent for each update of each context, to skip
+/// sending the same title, image, state or feedback again.
+#[derive(Clone, Default)]
+pub(crate) struct SentUpdates {
+    sent: Arc<Mutex<HashMap<String, HashMap<UpdateKey, u64>>>>,
+}
+
+fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl SentUpdates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record `value` for `update` and tell whether it differs from the last one sent.
+    pub async fn is_new<T: Hash + ?Sized>(
+        &self,
+        context: &str,
+        update: Update,
+        target: Option<&StreamDeckTarget>,
+        state: Option<&ActionState>,
+        value: &T,
+    ) -> bool {
+        let key = (
+            update,
+            target.map(|target| target.clone() as u16),
+            state.map(|state| state.clone() as u16),
+        );
+        let value = hash(value);
+        let mut sent = self.sent.lock().await;
+        let previous = sent
+            .entry(context.to_string())
+            .or_default()
+            .insert(key, value);
+        previous != Some(value)
+    }
+
+    /// Keep only the keys of `feedback` whose value changed since the last update.
+    pub async fn changed_feedback(&self, context: &str, feedback: Value) -> Option<Value> {
+        let Value::Object(entries) = feedback else {
+            return Some(feedback);
+        };
+        let mut changed = serde_json::Map::new();
+        for (key, value) in entries {
+            let update = Update::Feedback(key.clone());
+            if self
+                .is_new(context, update, None, None, &value.to_string())
+                .await
+            {
+                changed.insert(key, value);
+            }
+        }
+        (!changed.is_empty()).then_some(Value::Object(changed))
+    }
+
+    /// Record a value changed by the Stream Deck application itself, e.g. the
+    /// state toggled by a key press or a title edited by the user.
+    pub async fn record<T: Hash + ?Sized>(&self, context: &str, update: Update, value: &T) {
+        self.is_new(context, update, None, None, value).await;
+    }
+
+    pub async fn forget(&self, context: &str, update: &Update) {
+        if let Some(sent) = self.sent.lock().await.get_mut(context) {
+            sent.retain(|(kind, _, _), _| kind != update);
+        }
+    }
+
+    pub async fn forget_feedback(&self, context: &str) {
+        if let Some(sent) = self.sent.lock().await.get_mut(context) {
+            sent.retain(|(kind, _, _), _| !matches!(kind, Update::Feedback(_)));
+        }
+    }
+
+    pub async fn clear(&self, context: &str) {
+        self.sent.lock().await.remove(context);
+    }
+}
+
+impl StreamDeck {
+    pub(crate) async fn key_state_changed(&self, e: &KeyEvent, automatic_states: bool) {
+        if let Some(state) = e.state_after(automatic_states) {
+            self.sent_updates
+                .record(&e.context, Update::State, &state)
+                .await;
+        }
+    }
+
+    pub(crate) async fn title_changed(&self, e: &TitleParametersDidChangeEvent) {
+        self.sent_updates
+            .record(&e.context, Update::Title, &e.payload.title)
+            .await;
+    }
+
+    /// Send the next title, image, state and feedback of `context` even if
+    /// they are the same as the last ones.
+    pub async fn forget_sent_updates(&self, context: &str) {
+        self.sent_updates.clear(context).await;
+    }
+}
@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::args::{DeviceInfo, Info};
+pub use crate::args::{DeviceSize, DeviceType};
+
+/// Size in pixels of a key image on a standard Stream Deck at a device pixel ratio of 1.
+pub const KEY_SIZE: u32 = 72;
+
+/// Size in pixels of the key images drawn for an unknown device, sharp on high
+/// resolution devices too.
+pub const DEFAULT_KEY_IMAGE_SIZE: u32 = KEY_SIZE * 2;
+
+#[derive(Clone, Debug)]
+pub struct Device {
+    pub id: String,
+    pub name: Option<String>,
+    pub device_type: Option<DeviceType>,
+    pub raw_type: i32,
+    pub size: Option<DeviceSize>,
+    pub connected: bool,
+    pub device_pixel_ratio: u32,
+}
+
+impl Device {
+    fn from_info(id: String, info: DeviceInfo, device_pixel_ratio: u32) -> Self {
+        Device {
+            id,
+            name: info.name,
+            device_type: DeviceType::from_raw(info.device_type),
+            raw_type: info.device_type,
+            size: info.size,
+            connected: true,
+            device_pixel_ratio,
+        }
+    }
+
+    pub fn columns(&self) -> i32 {
+        self.size.map(|size| size.columns).unwrap_or(0)
+    }
+
+    pub fn rows(&self) -> i32 {
+        self.size.map(|size| size.rows).unwrap_or(0)
+    }
+
+    pub fn key_count(&self) -> i32 {
+        self.columns() * self.rows()
+    }
+
+    /// Side in pixels of a key image at the device resolution, `None` for keys without a display.
+    pub fn key_image_size(&self) -> Option<u32> {
+        let native = match self.device_type {
+            Some(DeviceType::StreamDeckPedal)
+            | Some(DeviceType::CorsairGKeys)
+            | Some(DeviceType::ScufController) => return None,
+            Some(DeviceType::StreamDeckMini) => 80,
+            Some(DeviceType::StreamDeckXL) | Some(DeviceType::StreamDeckNeo) => 96,
+            Some(DeviceType::StreamDeckPlus) => 120,
+            _ => KEY_SIZE,
+        };
+        Some(native.max(KEY_SIZE * self.device_pixel_ratio))
+    }
+
+    pub fn encoders(&self) -> i32 {
+        match self.device_type {
+            Some(DeviceType::StreamDeckPlus) => 4,
+            _ => 0,
+        }
+    }
+
+    pub fn has_touch_strip(&self) -> bool {
+        self.device_type == Some(DeviceType::StreamDeckPlus)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct DeviceRegistry {
+    devices: Arc<Mutex<HashMap<String, Device>>>,
+    device_pixel_ratio: u32,
+}
+
+impl DeviceRegistry {
+    /// Registry seeded with the devices received with `-info` at launch.
+    pub fn new(info: Option<&Info>) -> Self {
+        let device_pixel_ratio = info
+            .and_then(|info| info.device_pixel_ratio)
+            .filter(|ratio| *ratio > 0)
+            .unwrap_or(1) as u32;
+        let devices = info
+            .and_then(|info| info.devices.clone())
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|device| {
+                let id = device.id.clone()?;
+                Some((
+                    id.clone(),
+                    Device::from_info(id, device, device_pixel_ratio),
+                ))
+            })
+            .collect();
+        Self {
+            devices: Arc::new(Mutex::new(devices)),
+            device_pixel_ratio,
+        }
+    }
+
+    pub async fn get(&self, id: &str) -> Option<Device> {
+        self.devices.lock().await.get(id).cloned()
+    }
+
+    pub async fn all(&self) -> Vec<Device> {
+        self.devices.lock().await.values().cloned().collect()
+    }
+
+    pub async fn connected(&self) -> Vec<Device> {
+        self.devices
+            .lock()
+            .await
+            .values()
+            .filter(|device| device.connected)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn is_connected(&self, id: &str) -> bool {
+        self.get(id).await.map(|d| d.connected).unwrap_or(false)
+    }
+
+    pub(crate) async fn connect(&self, id: &str, info: DeviceInfo) {
+        let device = Device::from_info(id.to_string(), info, self.device_pixel_ratio);
+        self.devices.lock().await.insert(id.to_string(), device);
+    }
+
+    pub(crate) async fn disconnect(&self, id: &str) {
+        if let Some(device) = self.devices.lock().await.get_mut(id) {
+            device.connected = false;
+        }
+    }
+}
@@ -10,8 +10,8 @@ pub async fn download_image(
     let https = HttpsConnector::new();
     let client = Client::builder().build::<_, hyper::Body>(https);
     let mut request = Request::builder().method(Method::GET).uri(url);
-    if headers.is_some() {
-        for (key, value) in headers.unwrap().iter() {
+    if let Some(headers) = headers {
+        for (key, value) in headers.iter() {
             request = request.header(key, value);
         }
     }
@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod events;
 pub(crate) mod sent;
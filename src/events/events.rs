@@ -137,7 +137,7 @@ pub struct SetTitleImageEvent {
     pub(crate) payload: SetTitleImagePayload,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
 pub struct PayloadCoordinates {
     pub column: i32,
     pub row: i32,
@@ -242,7 +242,7 @@ pub struct TouchTapEvent {
     pub payload: TouchTapEventPayload,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum Controller {
     Keypad,
     Encoder,
@@ -266,7 +266,7 @@ pub struct AppearEvent {
     pub payload: AppearEventPayload,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum TitleAlignment {
     Bottom,
@@ -274,7 +274,7 @@ pub enum TitleAlignment {
     Middle,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct TitleParameters {
     pub font_family: Option<String>,
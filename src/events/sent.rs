@@ -145,10 +145,10 @@ pub fn get_settings_event(context: String) -> String {
     serde_json::to_string(&event).unwrap()
 }
 
-pub fn set_settings<T: serde::ser::Serialize>(context: &String, payload: T) -> String {
+pub fn set_settings<T: serde::ser::Serialize>(context: &str, payload: T) -> String {
     let event = SetSettingsEvent {
         event: "setSettings".to_string(),
-        context: context.clone(),
+        context: context.to_string(),
         payload,
     };
     serde_json::to_string(&event).unwrap()
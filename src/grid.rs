@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::events::events::{AppearEvent, Controller};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// A visible action instance placed on a device.
+#[derive(Clone, Debug)]
+pub struct GridKey {
+    pub context: String,
+    pub action: String,
+    pub device: String,
+    pub row: i32,
+    pub column: i32,
+    pub controller: Controller,
+}
+
+#[derive(Clone, Default)]
+pub struct Grid {
+    // device -> context -> key
+    keys: Arc<Mutex<HashMap<String, HashMap<String, GridKey>>>>,
+    // context -> (action, device)
+    multi_actions: Arc<Mutex<HashMap<String, (String, String)>>>,
+}
+
+impl Grid {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn find<F: Fn(&GridKey) -> bool>(&self, device: Option<&str>, filter: F) -> Vec<GridKey> {
+        let keys = self.keys.lock().await;
+        let mut found: Vec<GridKey> = keys
+            .iter()
+            .filter(|(id, _)| device.map(|device| device == *id).unwrap_or(true))
+            .flat_map(|(_, keys)| keys.values())
+            .filter(|key| filter(key))
+            .cloned()
+            .collect();
+        found.sort_by_key(|key| (key.row, key.column));
+        found
+    }
+
+    /// The key at `row`/`column` of `device`.
+    pub async fn at(&self, device: &str, row: i32, column: i32) -> Option<GridKey> {
+        self.find(Some(device), |key| {
+            key.controller == Controller::Keypad && key.row == row && key.column == column
+        })
+        .await
+        .pop()
+    }
+
+    /// The encoder at `column` of `device`.
+    pub async fn encoder_at(&self, device: &str, column: i32) -> Option<GridKey> {
+        self.find(Some(device), |key| {
+            key.controller == Controller::Encoder && key.column == column
+        })
+        .await
+        .pop()
+    }
+
+    pub async fn position_of(&self, context: &str) -> Option<GridKey> {
+        self.find(None, |key| key.context == context).await.pop()
+    }
+
+    /// Every key and encoder of `device`, sorted by row and column.
+    pub async fn of_device(&self, device: &str) -> Vec<GridKey> {
+        self.find(Some(device), |_| true).await
+    }
+
+    /// Every key and encoder of `action` on all the devices.
+    pub async fn of_action(&self, action: &str) -> Vec<GridKey> {
+        self.find(None, |key| key.action == action).await
+    }
+
+    pub async fn row(&self, device: &str, row: i32) -> Vec<GridKey> {
+        self.find(Some(device), |key| {
+            key.controller == Controller::Keypad && key.row == row
+        })
+        .await
+    }
+
+    pub async fn column(&self, device: &str, column: i32) -> Vec<GridKey> {
+        self.find(Some(device), |key| {
+            key.controller == Controller::Keypad && key.column == column
+        })
+        .await
+    }
+
+    /// The key next to `context` in `direction`, if one of the plugin actions is there.
+    pub async fn neighbor(&self, context: &str, direction: Direction) -> Option<GridKey> {
+        let key = self.position_of(context).await?;
+        let (row, column) = match direction {
+            Direction::Up => (key.row - 1, key.column),
+            Direction::Down => (key.row + 1, key.column),
+            Direction::Left => (key.row, key.column - 1),
+            Direction::Right => (key.row, key.column + 1),
+        };
+        match key.controller {
+            Controller::Keypad => self.at(&key.device, row, column).await,
+            Controller::Encoder if row == key.row => self.encoder_at(&key.device, column).await,
+            Controller::Encoder => None,
+        }
+    }
+
+    /// Keys next to `context` in the four directions.
+    pub async fn neighbors(&self, context: &str) -> Vec<GridKey> {
+        let mut neighbors = vec![];
+        for direction in [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+        ] {
+            if let Some(key) = self.neighbor(context, direction).await {
+                neighbors.push(key);
+            }
+        }
+        neighbors
+    }
+
+    /// Contexts of `action` placed inside multi actions, which have no position.
+    pub async fn multi_actions_of(&self, action: &str) -> Vec<String> {
+        self.multi_actions
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, (uuid, _))| uuid == action)
+            .map(|(context, _)| context.clone())
+            .collect()
+    }
+
+    pub(crate) async fn update(&self, e: &AppearEvent, visible: bool) {
+        if !visible {
+            self.multi_actions.lock().await.remove(&e.context);
+            if let Some(keys) = self.keys.lock().await.get_mut(&e.device) {
+                keys.remove(&e.context);
+            }
+            return;
+        }
+        if e.payload.is_in_multi_action {
+            self.multi_actions
+                .lock()
+                .await
+                .insert(e.context.clone(), (e.action.clone(), e.device.clone()));
+            return;
+        }
+        if let Some(coordinates) = &e.payload.coordinates {
+            let key = GridKey {
+                context: e.context.clone(),
+                action: e.action.clone(),
+                device: e.device.clone(),
+                row: coordinates.row,
+                column: coordinates.column,
+                controller: e.payload.controller.unwrap_or(Controller::Keypad),
+            };
+            self.keys
+                .lock()
+                .await
+                .entry(e.device.clone())
+                .or_default()
+                .insert(e.context.clone(), key);
+        }
+    }
+}
@@ -1,16 +1,276 @@
-use base64::{
-    alphabet,
-    engine::{self, general_purpose},
-    Engine as _,
-};
+use std::fmt;
+use std::io::Cursor;
 
+use base64::{engine::general_purpose, Engine as _};
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageOutputFormat, Rgb, RgbImage, RgbaImage};
+
+use crate::stream_deck::StreamDeck;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Bmp,
+    Svg,
+}
+
+impl ImageFormat {
+    /// Guess the format from the magic bytes at the start of `bytes`.
+    pub fn detect(bytes: &[u8]) -> Option<ImageFormat> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(ImageFormat::Webp)
+        } else if is_bmp(bytes) {
+            Some(ImageFormat::Bmp)
+        } else if is_svg(bytes) {
+            Some(ImageFormat::Svg)
+        } else {
+            None
+        }
+    }
+
+    pub fn mime(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::Gif => "image/gif",
+            ImageFormat::Webp => "image/webp",
+            ImageFormat::Bmp => "image/bmp",
+            ImageFormat::Svg => "image/svg+xml",
+        }
+    }
+}
+
+// "BM", the file header (reserved fields at zero) and a known DIB header size
+fn is_bmp(bytes: &[u8]) -> bool {
+    if bytes.len() < 18 || !bytes.starts_with(b"BM") {
+        return false;
+    }
+    let u32_at =
+        |i: usize| u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+    let reserved = u32_at(6);
+    let pixels_offset = u32_at(10);
+    let dib_size = u32_at(14);
+    reserved == 0
+        && matches!(dib_size, 12 | 16 | 40 | 52 | 56 | 64 | 108 | 124)
+        && pixels_offset >= 14 + dib_size
+}
+
+// `<svg`, or `<!DOCTYPE svg`, after an optional XML declaration and comments
+fn is_svg(bytes: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]);
+    let mut rest = head.trim_start_matches('\u{feff}').trim_start();
+    loop {
+        let end = if rest.starts_with("<?xml") {
+            rest.find("?>").map(|end| end + 2)
+        } else if rest.starts_with("<!--") {
+            rest.find("-->").map(|end| end + 3)
+        } else {
+            break;
+        };
+        match end {
+            Some(end) => rest = rest[end..].trim_start(),
+            None => return false,
+        }
+    }
+    rest.starts_with("<svg") || (rest.starts_with("<!DOCTYPE svg") && rest.contains("<svg"))
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    /// The bytes are not in one of the formats of [`ImageFormat`].
+    Unsupported,
+    Decode(String),
+    Encode(String),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Unsupported => write!(f, "unsupported image format"),
+            ImageError::Decode(e) => write!(f, "cannot decode image: {e}"),
+            ImageError::Encode(e) => write!(f, "cannot encode image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// Wrap image bytes in a data URI labeled with their detected MIME type,
+/// `None` if the format is not supported.
+///
+/// Before format detection every input was labeled `image/png`; unknown bytes
+/// now give `None`, which `set_image_b64` turns into a reset to the manifest
+/// image. Use [`image_to_data_uri`] to tell the two apart.
 pub fn image_to_base64(img: Option<Vec<u8>>) -> Option<String> {
-    if let Some(img) = img {
-        Some(format!(
-            "data:image/png;base64,{}",
-            general_purpose::STANDARD.encode(img)
-        ))
-    } else {
-        None
+    image_to_data_uri(&img?).ok()
+}
+
+/// Wrap image bytes in a data URI labeled with their detected MIME type.
+pub fn image_to_data_uri(img: &[u8]) -> Result<String, ImageError> {
+    let format = ImageFormat::detect(img).ok_or(ImageError::Unsupported)?;
+    Ok(format!(
+        "data:{};base64,{}",
+        format.mime(),
+        general_purpose::STANDARD.encode(img)
+    ))
+}
+
+/// Decode a PNG, JPEG, GIF (first frame), WebP or BMP image and encode it as PNG.
+pub fn transcode_to_png(img: &[u8]) -> Result<Vec<u8>, ImageError> {
+    match ImageFormat::detect(img) {
+        Some(ImageFormat::Png) => Ok(img.to_vec()),
+        Some(ImageFormat::Svg) | None => Err(ImageError::Unsupported),
+        Some(_) => {
+            let decoded =
+                image::load_from_memory(img).map_err(|e| ImageError::Decode(e.to_string()))?;
+            encode_png(&decoded)
+        }
+    }
+}
+
+/// Like [`image_to_data_uri`], transcoding bitmaps to PNG first.
+pub fn image_to_png_data_uri(img: &[u8]) -> Result<String, ImageError> {
+    match ImageFormat::detect(img) {
+        Some(ImageFormat::Svg) => image_to_data_uri(img),
+        _ => image_to_data_uri(&transcode_to_png(img)?),
+    }
+}
+
+/// Wrap SVG markup in a `data:image/svg+xml` URI.
+pub fn svg_to_base64(svg: &str) -> String {
+    format!(
+        "data:image/svg+xml;base64,{}",
+        general_purpose::STANDARD.encode(svg)
+    )
+}
+
+pub(crate) fn encode_png(img: &DynamicImage) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = Cursor::new(Vec::new());
+    img.write_to(&mut bytes, ImageOutputFormat::Png)
+        .map_err(|e| ImageError::Encode(e.to_string()))?;
+    Ok(bytes.into_inner())
+}
+
+/// Encode a decoded image as PNG and wrap it in a `data:image/png` URI.
+pub fn dynamic_image_to_base64(img: &DynamicImage) -> Option<String> {
+    image_to_base64(encode_png(img).ok())
+}
+
+/// Encoded size [`StreamDeck::set_image_resized`] aims below, in bytes.
+pub const DEFAULT_MAX_BYTES: usize = 64 * 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ResizeMode {
+    /// Scale the whole image inside the key, padding the rest with transparency.
+    Fit,
+    /// Scale the image to cover the key, cutting what overflows around the center.
+    Fill,
+    /// Keep the scale and cut the centered part of the image (scaling up only when smaller).
+    Crop,
+}
+
+/// Resize an image to a `size`x`size` key and encode it as PNG, or as JPEG
+/// when the PNG is bigger than `max_bytes`, lowering the quality until it fits.
+/// The budget is best-effort: the JPEG at quality 40 is returned even when it
+/// is still bigger. SVG images are passed through.
+pub fn resize_image(
+    img: &[u8],
+    size: u32,
+    mode: ResizeMode,
+    max_bytes: usize,
+) -> Result<String, ImageError> {
+    let format = ImageFormat::detect(img).ok_or(ImageError::Unsupported)?;
+    if format == ImageFormat::Svg {
+        return image_to_data_uri(img);
+    }
+    let decoded = image::load_from_memory(img).map_err(|e| ImageError::Decode(e.to_string()))?;
+    if decoded.width() == size
+        && decoded.height() == size
+        && img.len() <= max_bytes
+        && matches!(format, ImageFormat::Png | ImageFormat::Jpeg)
+    {
+        return image_to_data_uri(img);
+    }
+    let resized = resize(&decoded, size, mode);
+
+    let png = encode_png(&resized)?;
+    if png.len() <= max_bytes {
+        return image_to_data_uri(&png);
+    }
+    let flattened = flatten(&resized);
+    let mut jpeg = vec![];
+    for quality in [90, 80, 70, 60, 50, 40] {
+        jpeg = encode_jpeg(&flattened, quality)?;
+        if jpeg.len() <= max_bytes {
+            break;
+        }
+    }
+    image_to_data_uri(&jpeg)
+}
+
+pub(crate) fn resize(img: &DynamicImage, size: u32, mode: ResizeMode) -> DynamicImage {
+    match mode {
+        ResizeMode::Fit => {
+            let scaled = img.resize(size, size, FilterType::Lanczos3);
+            let mut canvas = RgbaImage::new(size, size);
+            let x = (size - scaled.width()) / 2;
+            let y = (size - scaled.height()) / 2;
+            imageops::overlay(&mut canvas, &scaled.to_rgba8(), x as i64, y as i64);
+            DynamicImage::ImageRgba8(canvas)
+        }
+        ResizeMode::Crop if img.width() >= size && img.height() >= size => img.crop_imm(
+            (img.width() - size) / 2,
+            (img.height() - size) / 2,
+            size,
+            size,
+        ),
+        ResizeMode::Fill | ResizeMode::Crop => img.resize_to_fill(size, size, FilterType::Lanczos3),
+    }
+}
+
+// JPEG has no alpha channel, transparent pixels become black like the key background
+fn flatten(img: &DynamicImage) -> DynamicImage {
+    let rgba = img.to_rgba8();
+    let rgb = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let alpha = a as u16;
+        Rgb([
+            (r as u16 * alpha / 255) as u8,
+            (g as u16 * alpha / 255) as u8,
+            (b as u16 * alpha / 255) as u8,
+        ])
+    });
+    DynamicImage::ImageRgb8(rgb)
+}
+
+fn encode_jpeg(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, ImageError> {
+    let mut bytes = Cursor::new(Vec::new());
+    img.write_to(&mut bytes, ImageOutputFormat::Jpeg(quality))
+        .map_err(|e| ImageError::Encode(e.to_string()))?;
+    Ok(bytes.into_inner())
+}
+
+impl StreamDeck {
+    /// Resize an image to the key resolution of the device showing `context`
+    /// (see [`resize_image`]) and send it.
+    pub async fn set_image_resized(
+        &self,
+        context: String,
+        image: &[u8],
+        mode: ResizeMode,
+    ) -> Result<(), ImageError> {
+        let size = self.key_image_size(&context).await;
+        let data = resize_image(image, size, mode, DEFAULT_MAX_BYTES)?;
+        self.set_image_b64(context, Some(data)).await;
+        Ok(())
     }
 }
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::events::events::{
+    AppearEvent, Controller, KeyEvent, PayloadCoordinates, TitleParameters,
+    TitleParametersDidChangeEvent,
+};
+
+/// What the Stream Deck application told us about a visible action instance.
+#[derive(Clone, Debug)]
+pub struct InstanceInfo {
+    pub context: String,
+    pub action: String,
+    pub device: String,
+    pub controller: Controller,
+    pub coordinates: Option<PayloadCoordinates>,
+    pub state: Option<i32>,
+    pub is_in_multi_action: bool,
+    pub title: Option<String>,
+    /// Title styling chosen by the user, known after the first `titleParametersDidChange`.
+    pub title_parameters: Option<TitleParameters>,
+}
+
+impl InstanceInfo {
+    pub fn is_encoder(&self) -> bool {
+        self.controller == Controller::Encoder
+    }
+
+    pub fn is_keypad(&self) -> bool {
+        self.controller == Controller::Keypad
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Instances {
+    instances: Arc<Mutex<HashMap<String, InstanceInfo>>>,
+}
+
+impl Instances {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn get(&self, context: &str) -> Option<InstanceInfo> {
+        self.instances.lock().await.get(context).cloned()
+    }
+
+    pub async fn of_action(&self, action: &str) -> Vec<InstanceInfo> {
+        self.instances
+            .lock()
+            .await
+            .values()
+            .filter(|instance| instance.action == action)
+            .cloned()
+            .collect()
+    }
+
+    pub async fn all(&self) -> Vec<InstanceInfo> {
+        self.instances.lock().await.values().cloned().collect()
+    }
+
+    pub(crate) async fn appear(&self, e: &AppearEvent) {
+        let instance = InstanceInfo {
+            context: e.context.clone(),
+            action: e.action.clone(),
+            device: e.device.clone(),
+            controller: e.payload.controller.unwrap_or(Controller::Keypad),
+            coordinates: e.payload.coordinates,
+            state: e.payload.state,
+            is_in_multi_action: e.payload.is_in_multi_action,
+            title: None,
+            title_parameters: None,
+        };
+        self.instances
+            .lock()
+            .await
+            .insert(e.context.clone(), instance);
+    }
+
+    pub(crate) async fn disappear(&self, context: &str) {
+        self.instances.lock().await.remove(context);
+    }
+
+    pub(crate) async fn key_up(&self, e: &KeyEvent, automatic_states: bool) {
+        if let Some(state) = e.state_after(automatic_states) {
+            self.set_state(&e.context, state).await;
+        }
+    }
+
+    pub(crate) async fn set_state(&self, context: &str, state: i32) {
+        if let Some(instance) = self.instances.lock().await.get_mut(context) {
+            instance.state = Some(state);
+        }
+    }
+
+    pub(crate) async fn title_parameters_changed(&self, e: &TitleParametersDidChangeEvent) {
+        if let Some(instance) = self.instances.lock().await.get_mut(&e.context) {
+            if e.payload.state.is_some() {
+                instance.state = e.payload.state;
+            }
+            if e.payload.coordinates.is_some() {
+                instance.coordinates = e.payload.coordinates;
+            }
+            instance.title = e.payload.title.clone();
+            if e.payload.title_parameters.is_some() {
+                instance.title_parameters = e.payload.title_parameters.clone();
+            }
+        }
+    }
+}
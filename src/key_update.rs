@@ -0,0 +1,110 @@
+use crate::dedupe::Update;
+use crate::events::events::{ActionState, StreamDeckTarget};
+use crate::events::sent::{set_image, set_state, set_title};
+use crate::outbox::update_key;
+use crate::stream_deck::StreamDeck;
+
+/// State, image and title of a key changed together, see [`StreamDeck::apply_key_update`].
+#[derive(Clone, Default)]
+pub struct KeyUpdate {
+    state: Option<i32>,
+    // `Some(None)` restores the image or title of the manifest
+    image: Option<Option<String>>,
+    title: Option<Option<String>>,
+    target: Option<StreamDeckTarget>,
+    for_state: Option<ActionState>,
+}
+
+impl KeyUpdate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn state(mut self, state: i32) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Image as a data URI.
+    pub fn image(mut self, image: String) -> Self {
+        self.image = Some(Some(image));
+        self
+    }
+
+    pub fn reset_image(mut self) -> Self {
+        self.image = Some(None);
+        self
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(Some(title.to_string()));
+        self
+    }
+
+    pub fn reset_title(mut self) -> Self {
+        self.title = Some(None);
+        self
+    }
+
+    /// Show the image and title on the hardware, the software or both.
+    pub fn target(mut self, target: StreamDeckTarget) -> Self {
+        self.target = Some(target);
+        self
+    }
+
+    /// Change the image and title of one state only. Without it, they apply to
+    /// every state of the key.
+    pub fn for_state(mut self, state: ActionState) -> Self {
+        self.for_state = Some(state);
+        self
+    }
+}
+
+impl StreamDeck {
+    /// Send the state, then the image, then the title of `update` back-to-back,
+    /// so the key changes at once. Parts equal to the last ones sent are skipped.
+    pub async fn apply_key_update(&self, context: String, update: KeyUpdate) {
+        let target = update.target.as_ref();
+        let for_state = update.for_state.as_ref();
+        let mut batch = vec![];
+        if let Some(state) = update.state {
+            self.instances.set_state(&context, state).await;
+            if self
+                .sent_updates
+                .is_new(&context, Update::State, None, None, &state)
+                .await
+            {
+                batch.push((
+                    update_key("setState", &context, None, None),
+                    set_state(context.clone(), state),
+                ));
+            }
+        }
+        if let Some(image) = update.image {
+            if self
+                .sent_updates
+                .is_new(&context, Update::Image, target, for_state, &image)
+                .await
+            {
+                batch.push((
+                    update_key("setImage", &context, target, for_state),
+                    set_image(context.clone(), image, target.cloned(), for_state.cloned()),
+                ));
+            }
+        }
+        if let Some(title) = update.title {
+            self.stop_marquee(&context).await;
+            if self
+                .sent_updates
+                .is_new(&context, Update::Title, target, for_state, &title)
+                .await
+            {
+                batch.push((
+                    update_key("setTitle", &context, target, for_state),
+                    set_title(context.clone(), title, target.cloned(), for_state.cloned()),
+                ));
+            }
+        }
+        self.outbox.push_batch(batch).await;
+    }
+}
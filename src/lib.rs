@@ -7,7 +7,6 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::SystemTime;
 use tokio::net::TcpStream;
-use tokio::sync::Mutex;
 use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
 use tungstenite::Message;
 
@@ -26,22 +25,57 @@ use crate::stream_deck::StreamDeck;
 pub mod action;
 pub mod action_manager;
 mod args;
+pub mod broadcast;
+pub mod confirm;
+mod dedupe;
+pub mod devices;
 pub mod events;
+pub mod grid;
+pub mod instances;
+pub mod key_update;
+mod lifecycle;
+pub mod links;
+pub mod outbox;
+pub mod radio;
+pub mod states;
 pub mod stream_deck;
+pub mod title;
 
 #[cfg(feature = "images")]
 pub mod images;
 
+#[cfg(feature = "images")]
+pub mod animation;
+
+#[cfg(feature = "images")]
+pub mod assets;
+
 #[cfg(feature = "download")]
 pub mod download;
 
+#[cfg(feature = "render")]
+pub mod overlay;
+
+#[cfg(feature = "qr")]
+pub mod qr;
+
+#[cfg(feature = "render")]
+pub mod render;
+
+#[cfg(feature = "render")]
+pub mod widgets;
+
+#[cfg(feature = "images")]
+pub mod svg;
+
+#[cfg(feature = "images")]
+pub mod tiles;
+
+#[cfg(feature = "images")]
+pub mod touch_strip;
+
 pub fn get_settings<T: serde::de::DeserializeOwned>(settings: HashMap<String, Value>) -> Option<T> {
-    let deserialized = T::deserialize(MapDeserializer::new(settings.into_iter()));
-    if deserialized.is_ok() {
-        Some(deserialized.unwrap())
-    } else {
-        None
-    }
+    T::deserialize(MapDeserializer::new(settings.into_iter())).ok()
 }
 
 pub struct Init {
@@ -64,7 +98,6 @@ impl Init {
         println!(" > plugin registered");
 
         let actions = self.manager.clone();
-        let press_events = Arc::new(Mutex::new(HashMap::<String, (SystemTime, bool)>::new()));
 
         let ws_read = ws_r
             .for_each(|r_msg| async {
@@ -76,7 +109,7 @@ impl Init {
 
                 let input: InputEvent = serde_json::from_str(&data).unwrap();
                 let manager = actions.clone();
-                let events_arc = press_events.clone();
+                let events_arc = stream_deck.press_events.clone();
                 let sd = stream_deck.clone();
 
                 tokio::spawn(async move {
@@ -89,6 +122,7 @@ impl Init {
                                     .unwrap();
                             sd.update_instances_settings(e.context.clone(), update)
                                 .await;
+                            sd.refresh_linked(&e.context).await;
                             manager
                                 .get(&e.action)
                                 .on_settings_changed(e.clone(), sd)
@@ -130,9 +164,12 @@ impl Init {
                                     let elapsed = now.elapsed().unwrap().as_millis() as f32;
                                     let mut events = events_arc.lock().await;
                                     let latest_event = events.get(&e.context.clone());
+                                    // the key disappeared while being pressed
+                                    if !sd.is_visible(&e.context).await {
+                                        break;
+                                    }
                                     // on key up was called before the long timeout
-                                    if latest_event.is_some() {
-                                        let (prev, _) = latest_event.unwrap();
+                                    if let Some((prev, _)) = latest_event {
                                         if prev > &now {
                                             break;
                                         }
@@ -159,11 +196,17 @@ impl Init {
                                     e.is_double_tap = true;
                                 }
                             }
-                            // update the latest event time
-                            events.insert(e.context.clone(), (SystemTime::now(), false));
+                            // update the latest event time (unless the key is already gone)
+                            if sd.is_visible(&e.context).await {
+                                events.insert(e.context.clone(), (SystemTime::now(), false));
+                            }
                             drop(events);
+                            let automatic_states = manager.get(&e.action).automatic_states();
+                            sd.instances.key_up(&e, automatic_states).await;
+                            sd.key_state_changed(&e, automatic_states).await;
                             // trigger the event modified or not (if needed)
                             if !should_skip {
+                                sd.radio_select(&e.context).await;
                                 manager.get(&e.action).on_key_up(e.clone(), sd).await;
                             }
                         }
@@ -177,41 +220,43 @@ impl Init {
                             manager.get(&e.action).on_dial_rotate(e, sd).await;
                         }
                         InputEvent::WillAppear(e) => {
-                            let id = e.action.clone();
-                            let arc_contexts = sd.contexts.clone();
-                            let mut contexts = arc_contexts.lock().await;
-                            sd.update_instances_settings(
-                                e.context.clone(),
-                                e.payload.settings.clone(),
-                            )
-                            .await;
-                            contexts
-                                .entry(id)
-                                .or_insert(Vec::new())
-                                .push(e.context.clone());
-                            drop(contexts);
-                            manager.get(&e.action).on_appear(e.clone(), sd).await;
+                            sd.instance_appeared(&e).await;
+                            sd.refresh_linked(&e.context).await;
+                            #[cfg(feature = "images")]
+                            sd.refresh_touch_strip(&e).await;
+                            #[cfg(feature = "images")]
+                            sd.refill_tiles(&e).await;
+                            manager
+                                .get(&e.action)
+                                .on_appear(e.clone(), sd.clone())
+                                .await;
+                            // after `on_appear`, where the groups are usually joined
+                            sd.refresh_radio(&e.context).await;
                         }
                         InputEvent::WillDisappear(e) => {
-                            let id = e.action.clone();
-                            let arc_contexts = sd.contexts.clone();
-                            let mut contexts = arc_contexts.lock().await;
-                            let contexts = contexts.entry(id).or_default();
-                            contexts.retain(|element| *element != e.context);
-                            manager.get(&e.action).on_disappear(e.clone(), sd).await;
+                            sd.instance_removed(&e).await;
+                            #[cfg(feature = "images")]
+                            sd.refresh_touch_strip(&e).await;
+                            let action = manager.get(&e.action);
+                            action.on_disappear(e.clone(), sd.clone()).await;
+                            action.on_instance_removed(e.context.clone(), sd).await;
                         }
                         InputEvent::TitleParametersDidChange(e) => {
+                            sd.instances.title_parameters_changed(&e).await;
+                            sd.title_changed(&e).await;
                             manager
                                 .get(&e.action)
                                 .on_title_parameters_changed(e.clone(), sd)
                                 .await;
                         }
                         InputEvent::DeviceDidConnect(e) => {
+                            sd.devices.connect(&e.device, e.device_info.clone()).await;
                             for (_k, action) in manager.actions.iter() {
                                 action.on_device_connect(e.clone(), sd.clone()).await;
                             }
                         }
                         InputEvent::DeviceDidDisconnect(e) => {
+                            sd.devices.disconnect(&e.device).await;
                             for (_k, action) in manager.actions.iter() {
                                 action.on_device_disconnect(e.clone(), sd.clone()).await;
                             }
@@ -244,10 +289,7 @@ impl Init {
                                 .await;
                         }
                         InputEvent::SendToPlugin(e) => {
-                            let shared = manager.shared();
-
-                            if shared.is_some() {
-                                let shared = shared.unwrap();
+                            if let Some(shared) = manager.shared() {
                                 shared.on_send_to_plugin(e.clone(), sd.clone()).await;
                             }
 
@@ -261,6 +303,7 @@ impl Init {
             })
             .fuse();
 
+        tokio::spawn(stream_deck.outbox.clone().run());
         stream_deck.clone().register().await;
 
         tokio::pin!(ws_read, fwd_to_ws);
@@ -290,9 +333,9 @@ pub async fn init(manager: ActionManager, ext_tx: Option<UnboundedSender<String>
     let (tx, rx) = futures::channel::mpsc::unbounded();
     let stream_deck = StreamDeck::new(args.clone(), tx.clone(), ext_tx);
 
-    return Init {
+    Init {
         stream_deck,
         args: (ws, rx),
         manager: Arc::new(manager),
-    };
+    }
 }
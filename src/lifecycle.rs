@@ -0,0 +1,52 @@
+use crate::events::events::AppearEvent;
+use crate::stream_deck::StreamDeck;
+
+/// Every piece of per-context state the SDK keeps is created on `willAppear`
+/// and purged on `willDisappear` here, so switching pages and profiles does not
+/// accumulate stale entries. Two exceptions are kept for the key to come back:
+/// looping animations (paused until [`StreamDeck::stop_animation`]) and the keys
+/// of a tiled image (until [`StreamDeck::clear_tiled_image`]).
+impl StreamDeck {
+    pub(crate) async fn instance_appeared(&self, e: &AppearEvent) {
+        // the application resets the key, nothing sent before is shown anymore
+        self.sent_updates.clear(&e.context).await;
+        self.update_instances_settings(e.context.clone(), e.payload.settings.clone())
+            .await;
+        self.contexts
+            .lock()
+            .await
+            .entry(e.action.clone())
+            .or_default()
+            .push(e.context.clone());
+        self.grid.update(e, true).await;
+        self.instances.appear(e).await;
+        #[cfg(feature = "images")]
+        self.resume_animation(&e.context).await;
+    }
+
+    pub(crate) async fn instance_removed(&self, e: &AppearEvent) {
+        let mut contexts = self.contexts.lock().await;
+        if let Some(action_contexts) = contexts.get_mut(&e.action) {
+            action_contexts.retain(|context| *context != e.context);
+            if action_contexts.is_empty() {
+                contexts.remove(&e.action);
+            }
+        }
+        drop(contexts);
+        self.instances_settings.lock().await.remove(&e.context);
+        self.press_events.lock().await.remove(&e.context);
+        self.stop_marquee(&e.context).await;
+        self.radio_groups.leave(&e.context).await;
+        self.sent_updates.clear(&e.context).await;
+        self.grid.update(e, false).await;
+        self.instances.disappear(&e.context).await;
+        // looping animations are paused, not dropped, to resume when the key comes back
+        #[cfg(feature = "images")]
+        self.pause_animation(&e.context).await;
+    }
+
+    /// Whether `context` is currently visible, i.e. between `willAppear` and `willDisappear`.
+    pub async fn is_visible(&self, context: &str) -> bool {
+        self.instances.get(context).await.is_some()
+    }
+}
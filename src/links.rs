@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::Mutex;
+
+use crate::instances::InstanceInfo;
+use crate::key_update::KeyUpdate;
+use crate::stream_deck::StreamDeck;
+
+/// Instance setting holding the link key, unless changed with [`Links::use_setting`].
+pub const DEFAULT_LINK_SETTING: &str = "link";
+
+type Render = Arc<dyn Fn(&Value, &InstanceInfo) -> Option<KeyUpdate> + Send + Sync>;
+
+/// Instances sharing a state object because their settings hold the same link
+/// key, e.g. the same "mute mic" action on several pages and devices.
+#[derive(Clone)]
+pub struct Links {
+    setting: Arc<Mutex<String>>,
+    // link key -> shared state
+    states: Arc<Mutex<HashMap<String, Value>>>,
+    // action UUID -> update drawing the shared state on a key
+    renders: Arc<Mutex<HashMap<String, Render>>>,
+}
+
+impl Default for Links {
+    fn default() -> Self {
+        Links {
+            setting: Arc::new(Mutex::new(DEFAULT_LINK_SETTING.to_string())),
+            states: Arc::new(Mutex::new(HashMap::new())),
+            renders: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl Links {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read the link key from the instance setting `key`.
+    pub async fn use_setting(&self, key: &str) {
+        *self.setting.lock().await = key.to_string();
+    }
+
+    /// How the instances of `action` show a shared state of type `T`, applied
+    /// to every linked key when the state changes or a key appears.
+    pub async fn render_with<T, F>(&self, action: &str, render: F)
+    where
+        T: DeserializeOwned,
+        F: Fn(&T, &InstanceInfo) -> KeyUpdate + Send + Sync + 'static,
+    {
+        let render: Render = Arc::new(move |state, instance| {
+            let state = T::deserialize(state).ok()?;
+            Some(render(&state, instance))
+        });
+        self.renders.lock().await.insert(action.to_string(), render);
+    }
+}
+
+impl StreamDeck {
+    /// Link key of `context`, from its settings.
+    pub async fn link_of(&self, context: &str) -> Option<String> {
+        let setting = self.links.setting.lock().await.clone();
+        let settings = self.instances_settings.lock().await;
+        match settings.get(context)?.get(&setting)? {
+            Value::String(link) if !link.is_empty() => Some(link.clone()),
+            _ => None,
+        }
+    }
+
+    /// Visible contexts linked by `link`.
+    pub async fn linked_contexts(&self, link: &str) -> Vec<String> {
+        let contexts: Vec<String> = self
+            .contexts
+            .lock()
+            .await
+            .values()
+            .flatten()
+            .cloned()
+            .collect();
+        let mut linked = vec![];
+        for context in contexts {
+            if self.link_of(&context).await.as_deref() == Some(link) {
+                linked.push(context);
+            }
+        }
+        linked
+    }
+
+    pub async fn shared_state<T: DeserializeOwned>(&self, link: &str) -> Option<T> {
+        let state = self.links.states.lock().await.get(link)?.clone();
+        T::deserialize(state).ok()
+    }
+
+    /// Replace the state shared by `link` and update every linked key.
+    pub async fn set_shared_state<T: Serialize>(&self, link: &str, state: &T) {
+        let state = match serde_json::to_value(state) {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+        self.links
+            .states
+            .lock()
+            .await
+            .insert(link.to_string(), state);
+        for context in self.linked_contexts(link).await {
+            self.refresh_linked(&context).await;
+        }
+    }
+
+    /// Change the state shared by `link` (starting from its default) and update every linked key.
+    pub async fn update_shared_state<T, F>(&self, link: &str, change: F)
+    where
+        T: Default + Serialize + DeserializeOwned,
+        F: FnOnce(&mut T),
+    {
+        let mut state = self.shared_state::<T>(link).await.unwrap_or_default();
+        change(&mut state);
+        self.set_shared_state(link, &state).await;
+    }
+
+    /// Draw the shared state on `context`, when it appears or its link changes.
+    pub(crate) async fn refresh_linked(&self, context: &str) {
+        let link = match self.link_of(context).await {
+            Some(link) => link,
+            None => return,
+        };
+        let state = match self.links.states.lock().await.get(&link) {
+            Some(state) => state.clone(),
+            None => return,
+        };
+        let instance = match self.instances.get(context).await {
+            Some(instance) => instance,
+            None => return,
+        };
+        let render = self
+            .links
+            .renders
+            .lock()
+            .await
+            .get(&instance.action)
+            .cloned();
+        if let Some(update) = render.and_then(|render| render(&state, &instance)) {
+            self.apply_key_update(context.to_string(), update).await;
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_channel::mpsc::UnboundedSender;
+use futures_util::SinkExt;
+use tokio::sync::{Mutex, Notify};
+use tungstenite::Message;
+
+use crate::events::events::{ActionState, StreamDeckTarget};
+use crate::stream_deck::StreamDeck;
+
+/// Messages sent to the Stream Deck application per second, unless changed
+/// with [`StreamDeck::set_max_messages_per_second`].
+pub const DEFAULT_MAX_MESSAGES_PER_SECOND: u32 = 60;
+
+// (coalescing key from `update_key`, message)
+type Update = (String, String);
+
+#[derive(Default)]
+struct Queues {
+    control: VecDeque<String>,
+    // updates of a batch are sent back-to-back
+    cosmetic: VecDeque<Vec<Update>>,
+}
+
+/// Outgoing messages waiting for the rate limit.
+#[derive(Clone)]
+pub(crate) struct Outbox {
+    tx: UnboundedSender<Message>,
+    queues: Arc<Mutex<Queues>>,
+    ready: Arc<Notify>,
+    interval: Arc<Mutex<Duration>>,
+}
+
+/// Key of the updates superseding each other in the cosmetic queue.
+pub(crate) fn update_key(
+    event: &str,
+    context: &str,
+    target: Option<&StreamDeckTarget>,
+    state: Option<&ActionState>,
+) -> String {
+    format!(
+        "{context}/{event}/{:?}/{:?}",
+        target.map(|target| target.clone() as u16),
+        state.map(|state| state.clone() as u16)
+    )
+}
+
+// the context comes first in the key and has no `/`
+fn context_of(key: &str) -> &str {
+    key.split('/').next().unwrap_or_default()
+}
+
+impl Outbox {
+    pub fn new(tx: UnboundedSender<Message>) -> Self {
+        Outbox {
+            tx,
+            queues: Arc::new(Mutex::new(Queues::default())),
+            ready: Arc::new(Notify::new()),
+            interval: Arc::new(Mutex::new(
+                Duration::from_secs(1) / DEFAULT_MAX_MESSAGES_PER_SECOND,
+            )),
+        }
+    }
+
+    /// Queue registration, settings, profiles, URLs and logs, always sent first.
+    pub async fn push_control(&self, content: String) {
+        self.queues.lock().await.control.push_back(content);
+        self.ready.notify_one();
+    }
+
+    /// Queue a title, image, state or feedback update, replacing the one with
+    /// the same `key` still waiting, see [`Outbox::push_batch`].
+    pub async fn push_cosmetic(&self, key: String, content: String) {
+        self.push_batch(vec![(key, content)]).await;
+    }
+
+    /// Queue updates sent back-to-back in order.
+    ///
+    /// A batch replacing queued updates takes their place, or right after the
+    /// updates of the same contexts queued since (e.g. feedback after its layout),
+    /// so updates of other keys never push it back. Other batches go last.
+    pub async fn push_batch(&self, batch: Vec<Update>) {
+        if batch.is_empty() {
+            return;
+        }
+        let replaces = |key: &String| batch.iter().any(|(replaced, _)| replaced == key);
+        let same_context = |key: &String| {
+            batch
+                .iter()
+                .any(|(update, _)| context_of(update) == context_of(key))
+        };
+        let mut queues = self.queues.lock().await;
+        let replaced = queues
+            .cosmetic
+            .iter()
+            .any(|queued| queued.iter().any(|(key, _)| replaces(key)));
+        let position = queues
+            .cosmetic
+            .iter()
+            .rposition(|queued| queued.iter().any(|(key, _)| same_context(key)));
+        match position {
+            Some(position) if replaced => {
+                for queued in queues.cosmetic.iter_mut() {
+                    queued.retain(|(key, _)| !replaces(key));
+                }
+                queues.cosmetic.insert(position + 1, batch);
+                queues.cosmetic.retain(|queued| !queued.is_empty());
+            }
+            _ => queues.cosmetic.push_back(batch),
+        }
+        drop(queues);
+        self.ready.notify_one();
+    }
+
+    pub async fn set_interval(&self, interval: Duration) {
+        *self.interval.lock().await = interval;
+    }
+
+    async fn next(&self) -> Vec<String> {
+        loop {
+            let mut queues = self.queues.lock().await;
+            if let Some(content) = queues.control.pop_front() {
+                return vec![content];
+            }
+            if let Some(batch) = queues.cosmetic.pop_front() {
+                return batch.into_iter().map(|(_, content)| content).collect();
+            }
+            drop(queues);
+            self.ready.notified().await;
+        }
+    }
+
+    /// Forward the queued messages to the socket, one per interval, until it closes.
+    pub async fn run(self) {
+        let mut tx = self.tx.clone();
+        loop {
+            let batch = self.next().await;
+            let count = batch.len() as u32;
+            for content in batch {
+                if tx.send(Message::Text(content)).await.is_err() {
+                    return;
+                }
+            }
+            let interval = *self.interval.lock().await;
+            tokio::time::sleep(interval * count).await;
+        }
+    }
+}
+
+impl StreamDeck {
+    /// Limit the messages sent to the Stream Deck application per second.
+    /// Control messages (settings, profiles, ...) go before the queued titles
+    /// and images, and only the latest queued update of a key is sent.
+    pub async fn set_max_messages_per_second(&self, max: u32) {
+        self.outbox
+            .set_interval(Duration::from_secs(1) / max.max(1))
+            .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbox() -> Outbox {
+        let (tx, _rx) = futures_channel::mpsc::unbounded();
+        Outbox::new(tx)
+    }
+
+    fn update(event: &str, context: &str, content: &str) -> Update {
+        (update_key(event, context, None, None), content.to_string())
+    }
+
+    async fn push(outbox: &Outbox, event: &str, context: &str, content: &str) {
+        let (key, content) = update(event, context, content);
+        outbox.push_cosmetic(key, content).await;
+    }
+
+    #[tokio::test]
+    async fn control_goes_before_cosmetic() {
+        let outbox = outbox();
+        push(&outbox, "setTitle", "a", "title").await;
+        outbox.push_control("settings".to_string()).await;
+        assert_eq!(outbox.next().await, vec!["settings"]);
+        assert_eq!(outbox.next().await, vec!["title"]);
+    }
+
+    #[tokio::test]
+    async fn superseding_update_keeps_its_slot() {
+        let outbox = outbox();
+        push(&outbox, "setFeedback", "dial", "1").await;
+        push(&outbox, "setImage", "a", "frame 1").await;
+        push(&outbox, "setImage", "b", "frame 1").await;
+        push(&outbox, "setFeedback", "dial", "2").await;
+        push(&outbox, "setImage", "a", "frame 2").await;
+        assert_eq!(outbox.next().await, vec!["2"]);
+        assert_eq!(outbox.next().await, vec!["frame 2"]);
+        assert_eq!(outbox.next().await, vec!["frame 1"]);
+    }
+
+    #[tokio::test]
+    async fn superseding_update_stays_after_its_context() {
+        let outbox = outbox();
+        push(&outbox, "setFeedback", "dial", "old").await;
+        push(&outbox, "setImage", "a", "image").await;
+        push(&outbox, "setFeedbackLayout", "dial", "layout").await;
+        push(&outbox, "setFeedback", "dial", "new").await;
+        assert_eq!(outbox.next().await, vec!["image"]);
+        assert_eq!(outbox.next().await, vec!["layout"]);
+        assert_eq!(outbox.next().await, vec!["new"]);
+    }
+
+    #[tokio::test]
+    async fn batch_replaces_queued_updates() {
+        let outbox = outbox();
+        push(&outbox, "setImage", "a", "old image").await;
+        push(&outbox, "setTitle", "b", "title").await;
+        outbox
+            .push_batch(vec![
+                update("setState", "a", "state"),
+                update("setImage", "a", "new image"),
+            ])
+            .await;
+        assert_eq!(outbox.next().await, vec!["state", "new image"]);
+        assert_eq!(outbox.next().await, vec!["title"]);
+    }
+}
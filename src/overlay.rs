@@ -0,0 +1,305 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tiny_skia::Pixmap;
+use tokio::sync::Mutex;
+
+use crate::assets::Assets;
+use crate::images::{image_to_base64, resize, ImageFormat, ResizeMode};
+use crate::render::{pixmap_from_image, text_width, Color, Font, KeyImage, Layer, Text};
+use crate::stream_deck::StreamDeck;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+/// Something composited over a base icon.
+#[derive(Clone, Debug)]
+pub enum Overlay {
+    /// A pill with a short text (e.g. an unread count).
+    Badge {
+        text: String,
+        color: Color,
+        text_color: Color,
+        corner: Corner,
+    },
+    /// A small status dot.
+    Dot { color: Color, corner: Corner },
+    /// A ring around the key filled from 12 o'clock, `value` goes from 0 to 1.
+    Progress { value: f32, color: Color },
+    /// A translucent color over the whole icon.
+    Tint(Color),
+}
+
+// position of an element of `width`x`height` in `corner`, in the 72x72 canvas
+fn corner_origin(corner: Corner, width: f32, height: f32) -> (f32, f32) {
+    const MARGIN: f32 = 3.0;
+    match corner {
+        Corner::TopLeft => (MARGIN, MARGIN),
+        Corner::TopRight => (72.0 - MARGIN - width, MARGIN),
+        Corner::BottomLeft => (MARGIN, 72.0 - MARGIN - height),
+        Corner::BottomRight => (72.0 - MARGIN - width, 72.0 - MARGIN - height),
+    }
+}
+
+impl Overlay {
+    fn layers(&self) -> Vec<Layer> {
+        match self {
+            Overlay::Badge {
+                text,
+                color,
+                text_color,
+                corner,
+            } => {
+                const HEIGHT: f32 = 20.0;
+                const SIZE: f32 = 14.0;
+                let Font(font) = Font::bundled();
+                let width = (text_width(&font, SIZE, text) + 10.0).max(HEIGHT);
+                let (x, y) = corner_origin(*corner, width, HEIGHT);
+                let mut label = Text::new(text, SIZE);
+                label.color = *text_color;
+                label.wrap = false;
+                label.padding = 0.0;
+                vec![
+                    Layer::Rect {
+                        x,
+                        y,
+                        width,
+                        height: HEIGHT,
+                        radius: HEIGHT / 2.0,
+                        color: *color,
+                    },
+                    Layer::Text(label.within(x, y, width, HEIGHT)),
+                ]
+            }
+            Overlay::Dot { color, corner } => {
+                const RADIUS: f32 = 7.0;
+                let (x, y) = corner_origin(*corner, RADIUS * 2.0, RADIUS * 2.0);
+                vec![Layer::Circle {
+                    x: x + RADIUS,
+                    y: y + RADIUS,
+                    radius: RADIUS,
+                    color: *color,
+                }]
+            }
+            Overlay::Progress { value, color } => vec![Layer::Arc {
+                x: 36.0,
+                y: 36.0,
+                radius: 32.0,
+                width: 5.0,
+                start: 0.0,
+                sweep: value.clamp(0.0, 1.0) * 360.0,
+                color: *color,
+            }],
+            Overlay::Tint(color) => vec![Layer::Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 72.0,
+                height: 72.0,
+                radius: 0.0,
+                color: *color,
+            }],
+        }
+    }
+}
+
+/// Bases kept decoded by a [`Compositor`] unless changed with [`Compositor::with_capacity`].
+pub const DEFAULT_CACHED_BASES: usize = 64;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum BaseId {
+    // hash of the bytes
+    Bytes(u64),
+    Asset(PathBuf),
+}
+
+struct Base {
+    pixmap: Arc<Pixmap>,
+    // value of `Bases::clock` when last used
+    used: u64,
+}
+
+#[derive(Default)]
+struct Bases {
+    bases: HashMap<(BaseId, u32), Base>,
+    clock: u64,
+}
+
+/// Composites overlays over base icons, keeping the most recently used bases
+/// decoded and resized so an update only draws the overlays.
+#[derive(Clone)]
+pub struct Compositor {
+    bases: Arc<Mutex<Bases>>,
+    capacity: usize,
+}
+
+impl Default for Compositor {
+    fn default() -> Self {
+        Compositor::with_capacity(DEFAULT_CACHED_BASES)
+    }
+}
+
+impl Compositor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep at most `capacity` bases, dropping the least recently used ones.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Compositor {
+            bases: Arc::new(Mutex::new(Bases::default())),
+            capacity: capacity.max(1),
+        }
+    }
+
+    async fn cached(&self, key: &(BaseId, u32)) -> Option<Arc<Pixmap>> {
+        let mut bases = self.bases.lock().await;
+        bases.clock += 1;
+        let clock = bases.clock;
+        let base = bases.bases.get_mut(key)?;
+        base.used = clock;
+        Some(base.pixmap.clone())
+    }
+
+    async fn insert(&self, key: (BaseId, u32), pixmap: Arc<Pixmap>) {
+        let mut bases = self.bases.lock().await;
+        while bases.bases.len() >= self.capacity {
+            let oldest = bases
+                .bases
+                .iter()
+                .min_by_key(|(_, base)| base.used)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(oldest) => bases.bases.remove(&oldest),
+                None => break,
+            };
+        }
+        bases.clock += 1;
+        let used = bases.clock;
+        bases.bases.insert(key, Base { pixmap, used });
+    }
+
+    async fn base(&self, image: &[u8], size: u32) -> Option<Arc<Pixmap>> {
+        let mut hasher = DefaultHasher::new();
+        image.hash(&mut hasher);
+        let key = (BaseId::Bytes(hasher.finish()), size);
+        if let Some(base) = self.cached(&key).await {
+            return Some(base);
+        }
+        let base = Arc::new(decode_base(image, size)?);
+        self.insert(key, base.clone()).await;
+        Some(base)
+    }
+
+    /// Draw `overlays` over `base` (PNG, JPEG, GIF, WebP, BMP or, with the `svg`
+    /// feature, SVG) at `size`x`size` and return it as a data URI.
+    pub async fn compose(&self, base: &[u8], size: u32, overlays: &[Overlay]) -> Option<String> {
+        let base = self.base(base, size).await?;
+        draw_overlays(&base, overlays)
+    }
+
+    /// Like [`Compositor::compose`] with the image at `path` in `assets` as the
+    /// base, in its `@2x` variant at 144 pixels and more. The file is read once,
+    /// until the base leaves the cache or [`Compositor::clear`].
+    pub async fn compose_asset<P: AsRef<Path>>(
+        &self,
+        assets: &Assets,
+        path: P,
+        size: u32,
+        overlays: &[Overlay],
+    ) -> io::Result<String> {
+        let resolved = assets.resolve_for_size(&path, size).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("asset {} not found", path.as_ref().display()),
+            )
+        })?;
+        let key = (BaseId::Asset(resolved.clone()), size);
+        let base = match self.cached(&key).await {
+            Some(base) => base,
+            None => {
+                let bytes = tokio::fs::read(&resolved).await?;
+                let base = decode_base(&bytes, size).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "unsupported image")
+                })?;
+                let base = Arc::new(base);
+                self.insert(key, base.clone()).await;
+                base
+            }
+        };
+        draw_overlays(&base, overlays)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "cannot encode image"))
+    }
+
+    /// Drop the cached bases.
+    pub async fn clear(&self) {
+        self.bases.lock().await.bases.clear();
+    }
+}
+
+fn draw_overlays(base: &Pixmap, overlays: &[Overlay]) -> Option<String> {
+    let mut pixmap = base.clone();
+    let layers = overlays
+        .iter()
+        .flat_map(|overlay| overlay.layers())
+        .collect();
+    KeyImage { layers }.draw_on(&mut pixmap);
+    image_to_base64(pixmap.encode_png().ok())
+}
+
+fn decode_base(image: &[u8], size: u32) -> Option<Pixmap> {
+    match ImageFormat::detect(image)? {
+        #[cfg(feature = "svg")]
+        ImageFormat::Svg => Pixmap::decode_png(&crate::svg::rasterize(
+            std::str::from_utf8(image).ok()?,
+            size,
+        )?)
+        .ok(),
+        #[cfg(not(feature = "svg"))]
+        ImageFormat::Svg => None,
+        _ => {
+            let decoded = image::load_from_memory(image).ok()?;
+            pixmap_from_image(&resize(&decoded, size, ResizeMode::Fit))
+        }
+    }
+}
+
+impl StreamDeck {
+    /// Composite `overlays` over the `base` icon at the key resolution and send it.
+    pub async fn set_image_with_overlays(
+        &self,
+        context: String,
+        base: &[u8],
+        overlays: &[Overlay],
+    ) {
+        let size = self.key_image_size(&context).await;
+        if let Some(data) = self.compositor.compose(base, size, overlays).await {
+            self.set_image_b64(context, Some(data)).await;
+        }
+    }
+
+    /// Like [`StreamDeck::set_image_with_overlays`] with the image at `path` in the
+    /// plugin folder as the base, see [`Compositor::compose_asset`].
+    pub async fn set_image_asset_with_overlays<P: AsRef<Path>>(
+        &self,
+        context: String,
+        path: P,
+        overlays: &[Overlay],
+    ) -> io::Result<()> {
+        let size = self.key_image_size(&context).await;
+        let data = self
+            .compositor
+            .compose_asset(&self.assets, path, size, overlays)
+            .await?;
+        self.set_image_b64(context, Some(data)).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,51 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use qrcode::{EcLevel, QrCode};
+
+use crate::images::{encode_png, image_to_data_uri, ImageError};
+use crate::stream_deck::StreamDeck;
+
+/// Light modules around the code required by scanners, in modules.
+const QUIET_ZONE: u32 = 4;
+
+/// Encode `text` as a QR code drawn on a white `size`x`size` key and return
+/// it as a `data:image/png` URI.
+///
+/// Modules are whole pixels so the code stays sharp, the remaining space is
+/// added to the quiet zone.
+pub fn qr_to_base64(text: &str, size: u32) -> Result<String, ImageError> {
+    let code = QrCode::with_error_correction_level(text, EcLevel::M)
+        .map_err(|e| ImageError::Encode(e.to_string()))?;
+    let width = code.width() as u32;
+    let module = size / (width + QUIET_ZONE * 2);
+    if module == 0 {
+        return Err(ImageError::Encode(format!(
+            "a {width}x{width} QR code does not fit in {size}x{size} pixels"
+        )));
+    }
+    let offset = (size - width * module) / 2;
+    let colors = code.to_colors();
+    let image = RgbaImage::from_fn(size, size, |x, y| {
+        let dark = x >= offset && y >= offset && {
+            let (column, row) = ((x - offset) / module, (y - offset) / module);
+            column < width
+                && row < width
+                && colors[(row * width + column) as usize] == qrcode::Color::Dark
+        };
+        if dark {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    });
+    image_to_data_uri(&encode_png(&DynamicImage::ImageRgba8(image))?)
+}
+
+impl StreamDeck {
+    /// Show `text` as a QR code at the key resolution of the device showing `context`.
+    pub async fn set_qr_code(&self, context: String, text: &str) -> Result<(), ImageError> {
+        let size = self.key_image_size(&context).await;
+        let data = qr_to_base64(text, size)?;
+        self.set_image_b64(context, Some(data)).await;
+        Ok(())
+    }
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use crate::stream_deck::StreamDeck;
+
+/// Mutually exclusive keys: the selected one is in state 1, the others in state 0.
+/// The keys of a group are best declared with `DisableAutomaticStates` in the
+/// manifest and `Action::automatic_states` returning `false`.
+#[derive(Clone, Default)]
+pub struct RadioGroups {
+    // context -> group
+    members: Arc<Mutex<HashMap<String, String>>>,
+    // group -> selected context, also for the members not visible
+    selected: Arc<Mutex<HashMap<String, String>>>,
+    // instance setting holding the group name
+    setting: Arc<Mutex<Option<String>>>,
+}
+
+impl RadioGroups {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Put `context` in `group`, usually from `on_appear` since the membership
+    /// is dropped when the context disappears.
+    pub async fn join(&self, context: &str, group: &str) {
+        self.members
+            .lock()
+            .await
+            .insert(context.to_string(), group.to_string());
+    }
+
+    pub async fn leave(&self, context: &str) {
+        self.members.lock().await.remove(context);
+    }
+
+    /// Read the group of the contexts not registered with [`RadioGroups::join`]
+    /// from the instance setting `key` (e.g. filled by a property inspector field).
+    pub async fn use_setting(&self, key: &str) {
+        *self.setting.lock().await = Some(key.to_string());
+    }
+}
+
+impl StreamDeck {
+    /// Group of `context`, registered or read from its settings.
+    pub async fn radio_group_of(&self, context: &str) -> Option<String> {
+        if let Some(group) = self.radio_groups.members.lock().await.get(context) {
+            return Some(group.clone());
+        }
+        let key = self.radio_groups.setting.lock().await.clone()?;
+        let settings = self.instances_settings.lock().await;
+        match settings.get(context)?.get(&key)? {
+            serde_json::Value::String(group) if !group.is_empty() => Some(group.clone()),
+            _ => None,
+        }
+    }
+
+    /// Visible contexts in `group`.
+    pub async fn radio_members(&self, group: &str) -> Vec<String> {
+        let contexts: Vec<String> = self
+            .contexts
+            .lock()
+            .await
+            .values()
+            .flatten()
+            .cloned()
+            .collect();
+        let mut members = vec![];
+        for context in contexts {
+            if self.radio_group_of(&context).await.as_deref() == Some(group) {
+                members.push(context);
+            }
+        }
+        members
+    }
+
+    /// Set `context` to state 1 and the other members of its group to state 0.
+    /// Called on `keyUp` for the keys in a group, unless the press was a long press.
+    /// Members on other pages or profiles are updated when they appear.
+    pub async fn radio_select(&self, context: &str) {
+        let group = match self.radio_group_of(context).await {
+            Some(group) => group,
+            None => return,
+        };
+        self.radio_groups
+            .selected
+            .lock()
+            .await
+            .insert(group.clone(), context.to_string());
+        for member in self.radio_members(&group).await {
+            let state = if member == context { 1 } else { 0 };
+            self.set_state(member, state).await;
+        }
+    }
+
+    /// The selected context of `group`, visible or not.
+    pub async fn radio_selected(&self, group: &str) -> Option<String> {
+        self.radio_groups.selected.lock().await.get(group).cloned()
+    }
+
+    /// Put an appearing member in the state matching the selection of its group.
+    pub(crate) async fn refresh_radio(&self, context: &str) {
+        let group = match self.radio_group_of(context).await {
+            Some(group) => group,
+            None => return,
+        };
+        let selected = match self.radio_selected(&group).await {
+            Some(selected) => selected,
+            None => return,
+        };
+        let state = if selected == context { 1 } else { 0 };
+        if self.state_of(context).await != Some(state) {
+            self.set_state(context.to_string(), state).await;
+        }
+    }
+}
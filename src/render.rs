@@ -0,0 +1,750 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use ab_glyph::{point, Font as _, FontArc, PxScale, ScaleFont};
+use tiny_skia::{
+    FillRule, FilterQuality, GradientStop, LineCap, LineJoin, LinearGradient, Paint, PathBuilder,
+    Pixmap, PixmapPaint, Point, PremultipliedColorU8, Rect, SpreadMode, Stroke, Transform,
+};
+use tokio::sync::Mutex;
+
+use crate::devices::KEY_SIZE;
+use crate::events::events::{TitleAlignment, TitleParameters};
+use crate::images::image_to_base64;
+use crate::stream_deck::StreamDeck;
+use crate::title::fit_title;
+
+/// RGBA color, parsed from the `#rrggbb` / `#rrggbbaa` strings used by the Stream Deck.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Color { r, g, b, a }
+    }
+
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.trim().trim_start_matches('#');
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        match hex.len() {
+            6 => Some(Color::rgb(channel(0)?, channel(2)?, channel(4)?)),
+            8 => Some(Color::rgba(
+                channel(0)?,
+                channel(2)?,
+                channel(4)?,
+                channel(6)?,
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn with_alpha(self, a: u8) -> Self {
+        Color { a, ..self }
+    }
+
+    fn to_skia(self) -> tiny_skia::Color {
+        tiny_skia::Color::from_rgba8(self.r, self.g, self.b, self.a)
+    }
+}
+
+/// A font usable by text layers, either the bundled one or loaded by the plugin.
+#[derive(Clone)]
+pub struct Font(pub(crate) FontArc);
+
+impl Font {
+    /// Ubuntu Light, bundled with the SDK.
+    pub fn bundled() -> Self {
+        Font(FontArc::try_from_slice(epaint_default_fonts::UBUNTU_LIGHT).unwrap())
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Option<Self> {
+        FontArc::try_from_vec(bytes).ok().map(Font)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Option<Self> {
+        Font::from_bytes(std::fs::read(path).ok()?)
+    }
+}
+
+/// Fonts loaded by the plugin for the families and styles users can pick
+/// for titles, the bundled font is used for the others.
+#[derive(Clone, Default)]
+pub struct Fonts {
+    fonts: Arc<Mutex<HashMap<(String, String), Font>>>,
+}
+
+impl Fonts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `font` for `family` in `style` ("Regular", "Bold", "Italic", "Bold Italic").
+    pub async fn register(&self, family: &str, style: &str, font: Font) {
+        self.fonts
+            .lock()
+            .await
+            .insert((family.to_lowercase(), style.to_lowercase()), font);
+    }
+
+    /// The font registered for `family` in `style`, or in any style when missing.
+    pub async fn get(&self, family: &str, style: &str) -> Option<Font> {
+        let fonts = self.fonts.lock().await;
+        let family = family.to_lowercase();
+        let style = match style.to_lowercase() {
+            style if style.is_empty() => "regular".to_string(),
+            style => style,
+        };
+        fonts
+            .get(&(family.clone(), style))
+            .or_else(|| fonts.get(&(family.clone(), "regular".to_string())))
+            .or_else(|| {
+                fonts
+                    .iter()
+                    .find(|((name, _), _)| *name == family)
+                    .map(|(_, font)| font)
+            })
+            .cloned()
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone)]
+pub struct Text {
+    pub text: String,
+    pub font: Option<Font>,
+    pub size: f32,
+    pub color: Color,
+    pub align: TextAlign,
+    pub vertical: TitleAlignment,
+    pub wrap: bool,
+    /// Space kept between the text and the key border.
+    pub padding: f32,
+    /// Box (x, y, width, height) the text is laid out in, the whole key if `None`.
+    pub area: Option<(f32, f32, f32, f32)>,
+    pub underline: bool,
+}
+
+impl Text {
+    /// Centered white text, wrapped at word boundaries.
+    pub fn new(text: &str, size: f32) -> Self {
+        Text {
+            text: text.to_string(),
+            font: None,
+            size,
+            color: Color::WHITE,
+            align: TextAlign::Center,
+            vertical: TitleAlignment::Middle,
+            wrap: true,
+            padding: 4.0,
+            area: None,
+            underline: false,
+        }
+    }
+
+    /// Text styled like a title with the user's [`TitleParameters`], lines are
+    /// only broken at `\n` like the Stream Deck does.
+    pub fn from_title(text: &str, parameters: &TitleParameters, font: Option<Font>) -> Self {
+        let mut title = Text::new(text, parameters.font_size.unwrap_or(12) as f32);
+        title.font = font;
+        title.wrap = false;
+        title.padding = 6.0;
+        title.underline = parameters.font_underline.unwrap_or(false);
+        if let Some(color) = parameters.title_color.as_deref().and_then(Color::from_hex) {
+            title.color = color;
+        }
+        title.vertical = parameters.title_alignment.unwrap_or(TitleAlignment::Bottom);
+        title
+    }
+
+    /// Center the text in a box of the 72x72 canvas.
+    pub fn within(mut self, x: f32, y: f32, width: f32, height: f32) -> Self {
+        self.align = TextAlign::Center;
+        self.vertical = TitleAlignment::Middle;
+        self.area = Some((x, y, width, height));
+        self
+    }
+}
+
+/// Something drawn on a key, coordinates and sizes are in a 72x72 canvas
+/// scaled to the resolution of the device.
+#[derive(Clone)]
+pub enum Layer {
+    Background(Color),
+    /// Gradient from the top to the bottom, or from left to right when not `vertical`.
+    Gradient {
+        from: Color,
+        to: Color,
+        vertical: bool,
+    },
+    Rect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radius: f32,
+        color: Color,
+    },
+    Circle {
+        x: f32,
+        y: f32,
+        radius: f32,
+        color: Color,
+    },
+    /// PNG image drawn (and scaled) into the given box.
+    Icon {
+        png: Arc<Vec<u8>>,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    /// Stroked arc, angles in degrees clockwise from 12 o'clock.
+    Arc {
+        x: f32,
+        y: f32,
+        radius: f32,
+        width: f32,
+        start: f32,
+        sweep: f32,
+        color: Color,
+    },
+    /// Open line through `points`.
+    Polyline {
+        points: Vec<(f32, f32)>,
+        width: f32,
+        color: Color,
+    },
+    Text(Text),
+}
+
+/// A key image composed from layers, drawn from the first to the last.
+#[derive(Clone, Default)]
+pub struct KeyImage {
+    pub layers: Vec<Layer>,
+}
+
+impl KeyImage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn layer(mut self, layer: Layer) -> Self {
+        self.layers.push(layer);
+        self
+    }
+
+    pub fn background(self, color: Color) -> Self {
+        self.layer(Layer::Background(color))
+    }
+
+    pub fn gradient(self, from: Color, to: Color) -> Self {
+        self.layer(Layer::Gradient {
+            from,
+            to,
+            vertical: true,
+        })
+    }
+
+    pub fn rect(self, x: f32, y: f32, width: f32, height: f32, color: Color) -> Self {
+        self.layer(Layer::Rect {
+            x,
+            y,
+            width,
+            height,
+            radius: 0.0,
+            color,
+        })
+    }
+
+    pub fn circle(self, x: f32, y: f32, radius: f32, color: Color) -> Self {
+        self.layer(Layer::Circle {
+            x,
+            y,
+            radius,
+            color,
+        })
+    }
+
+    pub fn icon(self, png: Vec<u8>, x: f32, y: f32, width: f32, height: f32) -> Self {
+        self.layer(Layer::Icon {
+            png: Arc::new(png),
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    pub fn text(self, text: Text) -> Self {
+        self.layer(Layer::Text(text))
+    }
+
+    /// Draw the layers on a `size`x`size` canvas.
+    pub fn draw(&self, size: u32) -> Option<Pixmap> {
+        let mut pixmap = Pixmap::new(size, size)?;
+        self.draw_on(&mut pixmap);
+        Some(pixmap)
+    }
+
+    /// Draw the layers over an existing square canvas.
+    pub fn draw_on(&self, pixmap: &mut Pixmap) {
+        let scale = pixmap.width() as f32 / KEY_SIZE as f32;
+        for layer in &self.layers {
+            draw_layer(pixmap, layer, scale);
+        }
+    }
+
+    /// Draw the layers on a `size`x`size` canvas and encode them as PNG.
+    pub fn render(&self, size: u32) -> Option<Vec<u8>> {
+        self.draw(size)?.encode_png().ok()
+    }
+
+    pub fn to_base64(&self, size: u32) -> Option<String> {
+        image_to_base64(self.render(size))
+    }
+}
+
+fn fill_paint(color: Color) -> Paint<'static> {
+    let mut paint = Paint::default();
+    paint.set_color(color.to_skia());
+    paint.anti_alias = true;
+    paint
+}
+
+fn draw_layer(pixmap: &mut Pixmap, layer: &Layer, scale: f32) {
+    let (width, height) = (pixmap.width() as f32, pixmap.height() as f32);
+    match layer {
+        Layer::Background(color) => {
+            if let Some(rect) = Rect::from_xywh(0.0, 0.0, width, height) {
+                pixmap.fill_rect(rect, &fill_paint(*color), Transform::identity(), None);
+            }
+        }
+        Layer::Gradient { from, to, vertical } => {
+            let end = if *vertical {
+                Point::from_xy(0.0, height)
+            } else {
+                Point::from_xy(width, 0.0)
+            };
+            let shader = LinearGradient::new(
+                Point::from_xy(0.0, 0.0),
+                end,
+                vec![
+                    GradientStop::new(0.0, from.to_skia()),
+                    GradientStop::new(1.0, to.to_skia()),
+                ],
+                SpreadMode::Pad,
+                Transform::identity(),
+            );
+            if let (Some(shader), Some(rect)) = (shader, Rect::from_xywh(0.0, 0.0, width, height)) {
+                let paint = Paint {
+                    shader,
+                    ..Paint::default()
+                };
+                pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+            }
+        }
+        Layer::Rect {
+            x,
+            y,
+            width,
+            height,
+            radius,
+            color,
+        } => {
+            let path = rounded_rect(
+                x * scale,
+                y * scale,
+                width * scale,
+                height * scale,
+                radius * scale,
+            );
+            if let Some(path) = path {
+                pixmap.fill_path(
+                    &path,
+                    &fill_paint(*color),
+                    FillRule::Winding,
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+        Layer::Circle {
+            x,
+            y,
+            radius,
+            color,
+        } => {
+            if let Some(path) = PathBuilder::from_circle(x * scale, y * scale, radius * scale) {
+                pixmap.fill_path(
+                    &path,
+                    &fill_paint(*color),
+                    FillRule::Winding,
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+        Layer::Icon {
+            png,
+            x,
+            y,
+            width,
+            height,
+        } => {
+            if let Ok(icon) = Pixmap::decode_png(png) {
+                let paint = PixmapPaint {
+                    quality: FilterQuality::Bicubic,
+                    ..PixmapPaint::default()
+                };
+                let transform = Transform::from_row(
+                    width * scale / icon.width() as f32,
+                    0.0,
+                    0.0,
+                    height * scale / icon.height() as f32,
+                    x * scale,
+                    y * scale,
+                );
+                pixmap.draw_pixmap(0, 0, icon.as_ref(), &paint, transform, None);
+            }
+        }
+        Layer::Arc {
+            x,
+            y,
+            radius,
+            width,
+            start,
+            sweep,
+            color,
+        } => {
+            let path = arc(x * scale, y * scale, radius * scale, *start, *sweep);
+            if let Some(path) = path {
+                let stroke = Stroke {
+                    width: width * scale,
+                    line_cap: LineCap::Round,
+                    ..Stroke::default()
+                };
+                pixmap.stroke_path(
+                    &path,
+                    &fill_paint(*color),
+                    &stroke,
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+        Layer::Polyline {
+            points,
+            width,
+            color,
+        } => {
+            let mut builder = PathBuilder::new();
+            for (i, (x, y)) in points.iter().enumerate() {
+                if i == 0 {
+                    builder.move_to(x * scale, y * scale);
+                } else {
+                    builder.line_to(x * scale, y * scale);
+                }
+            }
+            if let Some(path) = builder.finish() {
+                let stroke = Stroke {
+                    width: width * scale,
+                    line_cap: LineCap::Round,
+                    line_join: LineJoin::Round,
+                    ..Stroke::default()
+                };
+                pixmap.stroke_path(
+                    &path,
+                    &fill_paint(*color),
+                    &stroke,
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+        Layer::Text(text) => draw_text(pixmap, text, scale),
+    }
+}
+
+fn arc(x: f32, y: f32, radius: f32, start: f32, sweep: f32) -> Option<tiny_skia::Path> {
+    let sweep = sweep.clamp(-360.0, 360.0);
+    let steps = (sweep.abs() / 2.0).ceil().max(1.0) as usize;
+    let point = |degrees: f32| {
+        let radians = degrees.to_radians();
+        (x + radius * radians.sin(), y - radius * radians.cos())
+    };
+    let mut builder = PathBuilder::new();
+    let (px, py) = point(start);
+    builder.move_to(px, py);
+    for step in 1..=steps {
+        let (px, py) = point(start + sweep * step as f32 / steps as f32);
+        builder.line_to(px, py);
+    }
+    builder.finish()
+}
+
+/// Copy a decoded image into a canvas usable as a base for layers.
+pub(crate) fn pixmap_from_image(img: &image::DynamicImage) -> Option<Pixmap> {
+    let rgba = img.to_rgba8();
+    let mut pixmap = Pixmap::new(rgba.width(), rgba.height())?;
+    for (pixel, dst) in rgba.pixels().zip(pixmap.pixels_mut()) {
+        let [r, g, b, a] = pixel.0;
+        let premultiply = |c: u8| (c as u16 * a as u16 / 255) as u8;
+        if let Some(color) =
+            PremultipliedColorU8::from_rgba(premultiply(r), premultiply(g), premultiply(b), a)
+        {
+            *dst = color;
+        }
+    }
+    Some(pixmap)
+}
+
+fn rounded_rect(x: f32, y: f32, width: f32, height: f32, radius: f32) -> Option<tiny_skia::Path> {
+    let radius = radius.min(width / 2.0).min(height / 2.0).max(0.0);
+    if radius == 0.0 {
+        return Some(PathBuilder::from_rect(Rect::from_xywh(
+            x, y, width, height,
+        )?));
+    }
+    let (right, bottom) = (x + width, y + height);
+    let mut builder = PathBuilder::new();
+    builder.move_to(x + radius, y);
+    builder.line_to(right - radius, y);
+    builder.quad_to(right, y, right, y + radius);
+    builder.line_to(right, bottom - radius);
+    builder.quad_to(right, bottom, right - radius, bottom);
+    builder.line_to(x + radius, bottom);
+    builder.quad_to(x, bottom, x, bottom - radius);
+    builder.line_to(x, y + radius);
+    builder.quad_to(x, y, x + radius, y);
+    builder.close();
+    builder.finish()
+}
+
+/// Width in pixels of `text` written with `font` at `size` pixels.
+pub(crate) fn text_width(font: &FontArc, size: f32, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(size));
+    let mut width = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(previous) = previous {
+            width += scaled.kern(previous, id);
+        }
+        width += scaled.h_advance(id);
+        previous = Some(id);
+    }
+    width
+}
+
+/// Split `text` into lines no wider than `max_width`, breaking at word boundaries.
+pub(crate) fn wrap_lines(font: &FontArc, size: f32, text: &str, max_width: f32) -> Vec<String> {
+    let mut lines = vec![];
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{line} {word}")
+            };
+            if !line.is_empty() && text_width(font, size, &candidate) > max_width {
+                lines.push(line);
+                line = word.to_string();
+            } else {
+                line = candidate;
+            }
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+fn draw_text(pixmap: &mut Pixmap, text: &Text, scale: f32) {
+    let font = text.font.clone().unwrap_or_else(Font::bundled).0;
+    let size = text.size * scale;
+    let padding = text.padding * scale;
+    let (left, top, width, height) = match text.area {
+        Some((x, y, width, height)) => (x * scale, y * scale, width * scale, height * scale),
+        None => (0.0, 0.0, pixmap.width() as f32, pixmap.height() as f32),
+    };
+    let max_width = width - padding * 2.0;
+
+    let lines = if text.wrap {
+        wrap_lines(&font, size, &text.text, max_width)
+    } else {
+        text.text.split('\n').map(|line| line.to_string()).collect()
+    };
+
+    let scaled = font.as_scaled(PxScale::from(size));
+    let line_height = scaled.ascent() - scaled.descent() + scaled.line_gap();
+    let total = line_height * lines.len() as f32 - scaled.line_gap();
+    let mut top = top
+        + match text.vertical {
+            TitleAlignment::Top => padding,
+            TitleAlignment::Middle => (height - total) / 2.0,
+            TitleAlignment::Bottom => height - padding - total,
+        };
+
+    for line in lines {
+        let line_width = text_width(&font, size, &line);
+        let mut x = left
+            + match text.align {
+                TextAlign::Left => padding,
+                TextAlign::Center => (width - line_width) / 2.0,
+                TextAlign::Right => width - padding - line_width,
+            };
+        let baseline = top + scaled.ascent();
+        if text.underline {
+            let thickness = (size / 14.0).max(1.0);
+            let underline = Rect::from_xywh(x, baseline + size / 10.0, line_width, thickness);
+            if let Some(underline) = underline {
+                pixmap.fill_rect(
+                    underline,
+                    &fill_paint(text.color),
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+        let mut previous = None;
+        for c in line.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(previous) = previous {
+                x += scaled.kern(previous, id);
+            }
+            let glyph = id.with_scale_and_position(size, point(x, baseline));
+            x += scaled.h_advance(id);
+            previous = Some(id);
+            if let Some(outlined) = font.outline_glyph(glyph) {
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, coverage| {
+                    blend(
+                        pixmap,
+                        bounds.min.x as i32 + gx as i32,
+                        bounds.min.y as i32 + gy as i32,
+                        text.color,
+                        coverage,
+                    );
+                });
+            }
+        }
+        top += line_height;
+    }
+}
+
+fn blend(pixmap: &mut Pixmap, x: i32, y: i32, color: Color, coverage: f32) {
+    if x < 0 || y < 0 || x >= pixmap.width() as i32 || y >= pixmap.height() as i32 {
+        return;
+    }
+    let index = (y as u32 * pixmap.width() + x as u32) as usize;
+    let alpha = color.a as f32 / 255.0 * coverage.clamp(0.0, 1.0);
+    let dst = pixmap.pixels()[index];
+    let mix = |src: u8, dst: u8| (src as f32 * alpha + dst as f32 * (1.0 - alpha)).round() as u8;
+    let a = mix(255, dst.alpha());
+    let blended = PremultipliedColorU8::from_rgba(
+        mix(color.r, dst.red()).min(a),
+        mix(color.g, dst.green()).min(a),
+        mix(color.b, dst.blue()).min(a),
+        a,
+    );
+    if let Some(blended) = blended {
+        pixmap.pixels_mut()[index] = blended;
+    }
+}
+
+impl StreamDeck {
+    /// Draw `image` at the key resolution of the device showing `context` and send it.
+    pub async fn set_key_image(&self, context: String, image: &KeyImage) {
+        let size = self.key_image_size(&context).await;
+        if let Some(data) = image.to_base64(size) {
+            self.set_image_b64(context, Some(data)).await;
+        }
+    }
+
+    /// `title` (or the current title of `context` if `None`) styled with the
+    /// title parameters of `context`, or in 12 pt at the bottom until they
+    /// arrive. `None` if the user hid the title.
+    pub async fn title_text(&self, context: &str, title: Option<&str>) -> Option<Text> {
+        let instance = self.instances.get(context).await;
+        let title = title
+            .map(str::to_string)
+            .or_else(|| instance.as_ref()?.title.clone())?;
+        let parameters = match instance.and_then(|instance| instance.title_parameters) {
+            Some(parameters) => parameters,
+            None => {
+                let mut text = Text::new(&title, 12.0);
+                text.wrap = false;
+                text.padding = 6.0;
+                text.vertical = TitleAlignment::Bottom;
+                return Some(text);
+            }
+        };
+        if parameters.show_title == Some(false) {
+            return None;
+        }
+        let font = match &parameters.font_family {
+            Some(family) => {
+                let style = parameters.font_style.as_deref().unwrap_or_default();
+                self.fonts.get(family, style).await
+            }
+            None => None,
+        };
+        Some(Text::from_title(&title, &parameters, font))
+    }
+
+    /// Like [`StreamDeck::set_key_image`], drawing the title over `image` the way
+    /// the user styled it. Clear the native title (e.g. with an empty
+    /// `set_title`) to avoid drawing it twice.
+    pub async fn set_key_image_with_title(
+        &self,
+        context: String,
+        image: &KeyImage,
+        title: Option<&str>,
+    ) {
+        match self.title_text(&context, title).await {
+            Some(text) => {
+                let image = image.clone().text(text);
+                self.set_key_image(context, &image).await;
+            }
+            None => self.set_key_image(context, image).await,
+        }
+    }
+
+    /// Like [`StreamDeck::set_key_image_with_title`], wrapping a long title and
+    /// shrinking its font until it fits the key, see [`fit_title`].
+    pub async fn set_key_image_with_fitted_title(
+        &self,
+        context: String,
+        image: &KeyImage,
+        title: Option<&str>,
+    ) {
+        match self.title_text(&context, title).await {
+            Some(mut text) => {
+                (text.text, text.size) = fit_title(&text.text, text.size);
+                let image = image.clone().text(text);
+                self.set_key_image(context, &image).await;
+            }
+            None => self.set_key_image(context, image).await,
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use crate::events::events::KeyEvent;
+use crate::stream_deck::StreamDeck;
+
+impl KeyEvent {
+    /// State a two-state action should be in after this key press: the one
+    /// picked by the user in a multi-action, else the other state (the
+    /// Stream Deck application toggles the key itself after `keyUp`).
+    /// `None` for single-state actions.
+    pub fn desired_state(&self) -> Option<i32> {
+        if self.payload.is_in_multi_action {
+            if let Some(desired) = self.payload.user_desired_state {
+                return Some(desired);
+            }
+        }
+        self.payload
+            .state
+            .map(|state| if state == 0 { 1 } else { 0 })
+    }
+
+    /// State the key is in once this `keyUp` is handled: the desired state when
+    /// the application switches states itself, else the reported one.
+    pub(crate) fn state_after(&self, automatic_states: bool) -> Option<i32> {
+        if automatic_states {
+            self.desired_state()
+        } else {
+            self.payload.state
+        }
+    }
+}
+
+impl StreamDeck {
+    /// Last known state of `context`, from `willAppear`, `keyUp` and [`StreamDeck::set_state`].
+    /// Keys of actions with `DisableAutomaticStates` must say so with
+    /// `Action::automatic_states` for `keyUp` to be read right.
+    pub async fn state_of(&self, context: &str) -> Option<i32> {
+        self.instances.get(context).await?.state
+    }
+
+    /// Switch `context` to its other state.
+    pub async fn toggle_state(&self, context: String) {
+        let state = self.state_of(&context).await.unwrap_or(0);
+        self.set_state(context, if state == 0 { 1 } else { 0 })
+            .await;
+    }
+}
@@ -1,14 +1,37 @@
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use futures_channel::mpsc::UnboundedSender;
 use futures_util::SinkExt;
 use serde::de::value::MapDeserializer;
 use serde_json::Value;
 use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 use tungstenite::Message;
 
+#[cfg(feature = "images")]
+use crate::animation::Animator;
+#[cfg(feature = "images")]
+use crate::assets::Assets;
+#[cfg(feature = "render")]
+use crate::overlay::Compositor;
+#[cfg(feature = "render")]
+use crate::render::Fonts;
+#[cfg(feature = "images")]
+use crate::tiles::Tiles;
+#[cfg(feature = "images")]
+use crate::touch_strip::TouchStrip;
+
+use crate::args::Info;
+use crate::dedupe::{SentUpdates, Update};
+use crate::devices::{DeviceRegistry, DEFAULT_KEY_IMAGE_SIZE};
 use crate::events::sent::{get_global_settings_event, open_url, set_feedback, set_feedback_layout};
+use crate::grid::Grid;
+use crate::instances::{InstanceInfo, Instances};
+use crate::links::Links;
+use crate::outbox::{update_key, Outbox};
+use crate::radio::RadioGroups;
 use crate::{
     get_settings_event, log_message, register, send_to_property_inspector, set_global_settings,
     set_image, set_settings, set_state, set_title, show_alert, show_ok, switch_to_profile,
@@ -18,11 +41,31 @@ use crate::{
 #[derive(Clone)]
 pub struct StreamDeck {
     pub contexts: Arc<Mutex<HashMap<String, Vec<String>>>>,
+    pub devices: DeviceRegistry,
+    pub grid: Grid,
+    pub instances: Instances,
+    pub links: Links,
+    pub radio_groups: RadioGroups,
     args: StreamDeckArgs,
     pub(crate) global_settings: Arc<Mutex<HashMap<String, Value>>>,
     pub(crate) instances_settings: Arc<Mutex<HashMap<String, HashMap<String, Value>>>>,
-    tx: UnboundedSender<Message>,
+    pub(crate) press_events: Arc<Mutex<HashMap<String, (SystemTime, bool)>>>,
+    pub(crate) marquees: Arc<Mutex<HashMap<String, JoinHandle<()>>>>,
+    pub(crate) sent_updates: SentUpdates,
+    pub(crate) outbox: Outbox,
     ext_tx: Option<UnboundedSender<String>>,
+    #[cfg(feature = "images")]
+    pub assets: Assets,
+    #[cfg(feature = "images")]
+    pub touch_strip: TouchStrip,
+    #[cfg(feature = "images")]
+    pub tiles: Tiles,
+    #[cfg(feature = "images")]
+    pub animator: Animator,
+    #[cfg(feature = "render")]
+    pub compositor: Compositor,
+    #[cfg(feature = "render")]
+    pub fonts: Fonts,
 }
 
 impl StreamDeck {
@@ -37,38 +80,66 @@ impl StreamDeck {
     ) -> Self {
         Self {
             contexts: Arc::new(Mutex::new(HashMap::new())),
+            devices: DeviceRegistry::new(args.info.as_ref()),
+            grid: Grid::new(),
+            instances: Instances::new(),
+            links: Links::new(),
+            radio_groups: RadioGroups::new(),
             args,
-            tx,
+            outbox: Outbox::new(tx),
             global_settings: Arc::new(Mutex::new(HashMap::new())),
             instances_settings: Arc::new(Mutex::new(HashMap::new())),
+            press_events: Arc::new(Mutex::new(HashMap::new())),
+            marquees: Arc::new(Mutex::new(HashMap::new())),
+            sent_updates: SentUpdates::new(),
             ext_tx,
+            #[cfg(feature = "images")]
+            assets: Assets::of_plugin(),
+            #[cfg(feature = "images")]
+            touch_strip: TouchStrip::new(),
+            #[cfg(feature = "images")]
+            tiles: Tiles::new(),
+            #[cfg(feature = "images")]
+            animator: Animator::new(),
+            #[cfg(feature = "render")]
+            compositor: Compositor::new(),
+            #[cfg(feature = "render")]
+            fonts: Fonts::new(),
         }
     }
 
+    /// Application, plugin and devices info received with `-info` at launch.
+    pub fn info(&self) -> Option<Info> {
+        self.args.info.clone()
+    }
+
     pub async fn global_settings<T: serde::de::DeserializeOwned>(&self) -> Option<T> {
-        let deserialized = T::deserialize(MapDeserializer::new(
+        T::deserialize(MapDeserializer::new(
             self.global_settings.lock().await.clone().into_iter(),
-        ));
-        match deserialized {
-            Ok(deserialized) => Some(deserialized),
-            Err(_) => None,
-        }
+        ))
+        .ok()
     }
 
     pub async fn settings<T: serde::de::DeserializeOwned>(&self, context: String) -> Option<T> {
         let all_settings = self.instances_settings.lock().await;
-        let settings = all_settings.get(&context);
-        match settings {
-            Some(settings) => {
-                let deserialized =
-                    T::deserialize(MapDeserializer::new(settings.clone().into_iter()));
-                match deserialized {
-                    Ok(deserialized) => Some(deserialized),
-                    Err(_) => None,
-                }
-            }
+        let settings = all_settings.get(&context)?;
+        T::deserialize(MapDeserializer::new(settings.clone().into_iter())).ok()
+    }
+
+    /// Controller, coordinates, state and device of a visible instance.
+    pub async fn instance(&self, context: &str) -> Option<InstanceInfo> {
+        self.instances.get(context).await
+    }
+
+    /// Side in pixels of a key image on the device showing `context`.
+    pub async fn key_image_size(&self, context: &str) -> u32 {
+        let device = match self.instances.get(context).await {
+            Some(instance) => self.devices.get(&instance.device).await,
             None => None,
-        }
+        };
+        device
+            .and_then(|device| device.key_image_size())
+            .unwrap_or(DEFAULT_KEY_IMAGE_SIZE)
     }
 
     pub async fn external(&self, data: String) {
@@ -76,11 +147,12 @@ impl StreamDeck {
     }
 
     pub(crate) async fn send(&self, content: String) {
-        self.tx
-            .clone()
-            .send(Message::Text(content))
-            .await
-            .expect("Cannot send message");
+        self.outbox.push_control(content).await;
+    }
+
+    /// Send an update that a newer one with the same `key` supersedes.
+    pub(crate) async fn send_update(&self, key: String, content: String) {
+        self.outbox.push_cosmetic(key, content).await;
     }
 
     pub async fn register(&self) {
@@ -93,7 +165,17 @@ impl StreamDeck {
     pub async fn set_title(&self, context: String, title: Option<String>) {
         #[cfg(feature = "logging")]
         println!(" > set_title: {:?}", title);
-        self.send(set_title(context, title, None, None)).await;
+        self.stop_marquee(&context).await;
+        if !self
+            .sent_updates
+            .is_new(&context, Update::Title, None, None, &title)
+            .await
+        {
+            return;
+        }
+        let key = update_key("setTitle", &context, None, None);
+        self.send_update(key, set_title(context, title, None, None))
+            .await;
     }
 
     pub async fn contexts_of(&self, uuid: &str) -> Vec<String> {
@@ -112,15 +194,41 @@ impl StreamDeck {
         target: Option<StreamDeckTarget>,
         state: Option<ActionState>,
     ) {
-        self.send(set_title(context, title, target, state)).await;
+        self.stop_marquee(&context).await;
+        if !self
+            .sent_updates
+            .is_new(
+                &context,
+                Update::Title,
+                target.as_ref(),
+                state.as_ref(),
+                &title,
+            )
+            .await
+        {
+            return;
+        }
+        let key = update_key("setTitle", &context, target.as_ref(), state.as_ref());
+        self.send_update(key, set_title(context, title, target, state))
+            .await;
     }
 
     pub async fn set_image_b64(&self, context: String, base64: Option<String>) {
-        self.send(set_image(context, base64, None, None)).await;
+        if !self
+            .sent_updates
+            .is_new(&context, Update::Image, None, None, &base64)
+            .await
+        {
+            return;
+        }
+        let key = update_key("setImage", &context, None, None);
+        self.send_update(key, set_image(context, base64, None, None))
+            .await;
     }
 
     pub async fn show_ok(&self, context: String) {
-        self.send(show_ok(context)).await;
+        let key = update_key("showOk", &context, None, None);
+        self.send_update(key, show_ok(context)).await;
     }
 
     pub async fn log(&self, message: String) {
@@ -128,7 +236,8 @@ impl StreamDeck {
     }
 
     pub async fn show_alert(&self, context: String) {
-        self.send(show_alert(context)).await;
+        let key = update_key("showAlert", &context, None, None);
+        self.send_update(key, show_alert(context)).await;
     }
 
     pub async fn switch_to_profile(&self, device: String, profile: String) {
@@ -151,15 +260,37 @@ impl StreamDeck {
     }
 
     pub async fn set_state(&self, context: String, state: i32) {
-        self.send(set_state(context, state)).await;
+        self.instances.set_state(&context, state).await;
+        if !self
+            .sent_updates
+            .is_new(&context, Update::State, None, None, &state)
+            .await
+        {
+            return;
+        }
+        let key = update_key("setState", &context, None, None);
+        self.send_update(key, set_state(context, state)).await;
     }
 
+    /// Send the keys of `feedback` that changed since the last update.
     pub async fn set_feedback(&self, context: String, feedback: Value) {
-        self.send(set_feedback(context, feedback)).await;
+        if let Some(feedback) = self.sent_updates.changed_feedback(&context, feedback).await {
+            // only an update of the same feedback keys supersedes a queued one
+            let keys = match &feedback {
+                Value::Object(entries) => entries.keys().cloned().collect::<Vec<_>>().join(","),
+                _ => String::new(),
+            };
+            let key = update_key(&format!("setFeedback[{keys}]"), &context, None, None);
+            self.send_update(key, set_feedback(context, feedback)).await;
+        }
     }
 
     pub async fn set_feedback_layout(&self, context: String, layout: String) {
-        self.send(set_feedback_layout(context, layout)).await;
+        // a new layout starts from its default values
+        self.sent_updates.forget_feedback(&context).await;
+        let key = update_key("setFeedbackLayout", &context, None, None);
+        self.send_update(key, set_feedback_layout(context, layout))
+            .await;
     }
 
     pub async fn open_url(&self, url: String) {
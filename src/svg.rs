@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+
+use crate::images::svg_to_base64;
+use crate::stream_deck::StreamDeck;
+
+/// Values substituted in the `{{name}}` placeholders of an [`SvgTemplate`].
+#[derive(Clone, Default, Debug)]
+pub struct SvgValues(HashMap<String, String>);
+
+impl SvgValues {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Text, escaped so it cannot break the markup.
+    pub fn text(mut self, name: &str, value: &str) -> Self {
+        self.0.insert(name.to_string(), escape(value));
+        self
+    }
+
+    /// A `#rgb`, `#rrggbb`, `#rrggbbaa` or named color, invalid colors are ignored.
+    pub fn color(mut self, name: &str, value: &str) -> Self {
+        if is_color(value) {
+            self.0.insert(name.to_string(), value.to_string());
+        }
+        self
+    }
+
+    pub fn number(mut self, name: &str, value: f64) -> Self {
+        let value = format!("{:.3}", value)
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string();
+        self.0.insert(name.to_string(), value);
+        self
+    }
+}
+
+/// SVG markup with `{{name}}` placeholders, filled on every update.
+#[derive(Clone, Debug)]
+pub struct SvgTemplate {
+    source: String,
+}
+
+impl SvgTemplate {
+    pub fn new(source: &str) -> Self {
+        SvgTemplate {
+            source: source.to_string(),
+        }
+    }
+
+    /// Names of the placeholders found in the template.
+    pub fn placeholders(&self) -> Vec<String> {
+        let mut names = vec![];
+        let mut rest = self.source.as_str();
+        while let Some(start) = rest.find("{{") {
+            rest = &rest[start + 2..];
+            match rest.find("}}") {
+                Some(end) => {
+                    let name = rest[..end].trim().to_string();
+                    if !names.contains(&name) {
+                        names.push(name);
+                    }
+                    rest = &rest[end + 2..];
+                }
+                None => break,
+            }
+        }
+        names
+    }
+
+    /// The SVG markup with the placeholders replaced, missing values are left empty.
+    pub fn render(&self, values: &SvgValues) -> String {
+        let mut svg = String::with_capacity(self.source.len());
+        let mut rest = self.source.as_str();
+        while let Some(start) = rest.find("{{") {
+            svg.push_str(&rest[..start]);
+            let after = &rest[start + 2..];
+            match after.find("}}") {
+                Some(end) => {
+                    if let Some(value) = values.0.get(after[..end].trim()) {
+                        svg.push_str(value);
+                    }
+                    rest = &after[end + 2..];
+                }
+                None => {
+                    svg.push_str(&rest[start..]);
+                    rest = "";
+                }
+            }
+        }
+        svg.push_str(rest);
+        svg
+    }
+
+    pub fn to_base64(&self, values: &SvgValues) -> String {
+        svg_to_base64(&self.render(values))
+    }
+
+    /// Render the template and rasterize it to a `size`x`size` PNG.
+    #[cfg(feature = "svg")]
+    pub fn to_png(&self, values: &SvgValues, size: u32) -> Option<Vec<u8>> {
+        rasterize(&self.render(values), size)
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn is_color(value: &str) -> bool {
+    match value.strip_prefix('#') {
+        Some(hex) => {
+            matches!(hex.len(), 3 | 4 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+        }
+        None => !value.is_empty() && value.chars().all(|c| c.is_ascii_alphabetic()),
+    }
+}
+
+/// Rasterize SVG markup to a `size`x`size` PNG, text is drawn with the system fonts.
+/// Non-square drawings are scaled to fit and centered.
+#[cfg(feature = "svg")]
+pub fn rasterize(svg: &str, size: u32) -> Option<Vec<u8>> {
+    use std::sync::OnceLock;
+
+    use resvg::tiny_skia::{Pixmap, Transform};
+    use resvg::usvg::{self, fontdb, TreeParsing, TreePostProc};
+
+    static FONTS: OnceLock<fontdb::Database> = OnceLock::new();
+    let fonts = FONTS.get_or_init(|| {
+        let mut fonts = fontdb::Database::new();
+        fonts.load_system_fonts();
+        // the generic family defaults (Arial, Times New Roman) are often missing
+        let query = fontdb::Query {
+            families: &[fontdb::Family::SansSerif],
+            ..fontdb::Query::default()
+        };
+        if fonts.query(&query).is_none() {
+            let family = fonts
+                .faces()
+                .find_map(|face| face.families.first().map(|(family, _)| family.clone()));
+            if let Some(family) = family {
+                fonts.set_sans_serif_family(family);
+            }
+        }
+        fonts
+    });
+    let options = usvg::Options {
+        font_family: "sans-serif".to_string(),
+        ..usvg::Options::default()
+    };
+
+    let mut tree = usvg::Tree::from_str(svg, &options).ok()?;
+    tree.postprocess(usvg::PostProcessingSteps::default(), fonts);
+    let mut pixmap = Pixmap::new(size, size)?;
+    // fit the drawing in the square without stretching it, centered
+    let (width, height) = (tree.size.width(), tree.size.height());
+    let scale = (size as f32 / width).min(size as f32 / height);
+    let transform = Transform::from_translate(
+        (size as f32 - width * scale) / 2.0,
+        (size as f32 - height * scale) / 2.0,
+    )
+    .pre_scale(scale, scale);
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    pixmap.encode_png().ok()
+}
+
+impl StreamDeck {
+    /// Fill `template` with `values` and send it as a `data:image/svg+xml` image.
+    pub async fn set_svg(&self, context: String, template: &SvgTemplate, values: &SvgValues) {
+        self.set_image_b64(context, Some(template.to_base64(values)))
+            .await;
+    }
+
+    /// Fill `template` with `values` and send it as a PNG at the key resolution,
+    /// for devices or versions that need bitmaps.
+    #[cfg(feature = "svg")]
+    pub async fn set_svg_png(&self, context: String, template: &SvgTemplate, values: &SvgValues) {
+        let size = self.key_image_size(&context).await;
+        if let Some(png) = template.to_png(values, size) {
+            self.set_image_b64(context, crate::images::image_to_base64(Some(png)))
+                .await;
+        }
+    }
+}
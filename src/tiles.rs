@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use image::imageops::FilterType;
+use tokio::sync::Mutex;
+
+use crate::args::DeviceSize;
+use crate::devices::DEFAULT_KEY_IMAGE_SIZE;
+use crate::events::events::{AppearEvent, Controller};
+use crate::images::dynamic_image_to_base64;
+use crate::stream_deck::StreamDeck;
+
+/// Rectangle of keys on a device, starting at `row`/`column` (top left).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TileRegion {
+    pub device: String,
+    pub row: i32,
+    pub column: i32,
+    pub rows: i32,
+    pub columns: i32,
+}
+
+impl TileRegion {
+    pub fn contains(&self, row: i32, column: i32) -> bool {
+        row >= self.row
+            && row < self.row + self.rows
+            && column >= self.column
+            && column < self.column + self.columns
+    }
+
+    fn same_origin(&self, other: &TileRegion) -> bool {
+        self.device == other.device && self.row == other.row && self.column == other.column
+    }
+}
+
+struct Wall {
+    region: TileRegion,
+    // context -> data uri, for the keys in the region when it was set
+    tiles: HashMap<String, String>,
+}
+
+#[derive(Clone, Default)]
+pub struct Tiles {
+    walls: Arc<Mutex<Vec<Wall>>>,
+}
+
+impl Tiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StreamDeck {
+    /// Spread one image over a rectangle of keys, like a video wall.
+    ///
+    /// The region is clipped to the device size reported in `DeviceInfo.size`,
+    /// the image is scaled to cover it and each key gets its own crop via `setImage`.
+    /// The keys in the region when it is set get their crop again whenever they
+    /// reappear, e.g. after a page switch; keys at the same coordinates on other
+    /// pages or profiles are left alone.
+    pub async fn set_tiled_image(&self, region: TileRegion, image: Vec<u8>) -> bool {
+        let device = self.devices.get(&region.device).await;
+        let region = match clip_region(region, device.as_ref().and_then(|d| d.size)) {
+            Some(region) => region,
+            None => return false,
+        };
+        let decoded = match image::load_from_memory(&image) {
+            Ok(decoded) => decoded,
+            Err(_) => return false,
+        };
+        let key = match device {
+            Some(device) => match device.key_image_size() {
+                Some(key) => key,
+                None => return false,
+            },
+            None => DEFAULT_KEY_IMAGE_SIZE,
+        };
+        let decoded = decoded.resize_to_fill(
+            key * region.columns as u32,
+            key * region.rows as u32,
+            FilterType::Triangle,
+        );
+
+        let mut tiles = HashMap::new();
+        for grid_key in self.grid.of_device(&region.device).await {
+            if grid_key.controller != Controller::Keypad
+                || !region.contains(grid_key.row, grid_key.column)
+            {
+                continue;
+            }
+            let (row, column) = (
+                (grid_key.row - region.row) as u32,
+                (grid_key.column - region.column) as u32,
+            );
+            let tile = decoded.crop_imm(column * key, row * key, key, key);
+            if let Some(data) = dynamic_image_to_base64(&tile) {
+                tiles.insert(grid_key.context, data);
+            }
+        }
+
+        let mut walls = self.tiles.walls.lock().await;
+        walls.retain(|wall| !wall.region.same_origin(&region));
+        walls.push(Wall {
+            region,
+            tiles: tiles.clone(),
+        });
+        drop(walls);
+
+        for (context, data) in tiles {
+            self.set_image_b64(context, Some(data)).await;
+        }
+        true
+    }
+
+    /// Stop refilling the keys of `region`, the keys keep their last image.
+    pub async fn clear_tiled_image(&self, region: &TileRegion) {
+        let mut walls = self.tiles.walls.lock().await;
+        walls.retain(|wall| !wall.region.same_origin(region));
+    }
+
+    pub(crate) async fn refill_tiles(&self, e: &AppearEvent) {
+        let walls = self.tiles.walls.lock().await;
+        let tile = walls
+            .iter()
+            .rev()
+            .find_map(|wall| wall.tiles.get(&e.context).cloned());
+        drop(walls);
+
+        if let Some(data) = tile {
+            self.set_image_b64(e.context.clone(), Some(data)).await;
+        }
+    }
+}
+
+fn clip_region(mut region: TileRegion, size: Option<DeviceSize>) -> Option<TileRegion> {
+    if let Some(size) = size {
+        region.rows = region.rows.min(size.rows - region.row);
+        region.columns = region.columns.min(size.columns - region.column);
+    }
+    if region.row < 0 || region.column < 0 || region.rows <= 0 || region.columns <= 0 {
+        return None;
+    }
+    Some(region)
+}
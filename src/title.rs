@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use crate::dedupe::Update;
+use crate::events::sent::set_title;
+use crate::outbox::update_key;
+use crate::stream_deck::StreamDeck;
+
+/// Lines the Stream Deck can show in a key title.
+pub const MAX_TITLE_LINES: usize = 3;
+
+/// Smallest font size [`fit_title`] shrinks to.
+pub const MIN_TITLE_FONT_SIZE: f32 = 7.0;
+
+/// Time between two steps of a marquee title.
+pub const MARQUEE_INTERVAL: Duration = Duration::from_millis(300);
+
+// usable title width on a 72x72 key
+const TITLE_WIDTH: f32 = 64.0;
+
+// font size used by the Stream Deck application when none is known
+const DEFAULT_FONT_SIZE: f32 = 12.0;
+
+const MARQUEE_GAP: &str = "   ";
+
+/// What to do with a title that does not fit in [`MAX_TITLE_LINES`] lines.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TitleOverflow {
+    /// Cut the last line with an ellipsis.
+    Ellipsis,
+    /// Scroll the title on a single line.
+    Marquee,
+}
+
+/// Approximate width of `text` in pixels at `font_size`, from the average
+/// width of the glyphs of the common sans-serif fonts.
+pub fn estimate_width(text: &str, font_size: f32) -> f32 {
+    text.chars()
+        .map(|c| match c {
+            'i' | 'l' | 'j' | 'I' | '!' | '|' | '.' | ',' | ':' | ';' | '\'' => 0.28,
+            ' ' | 'f' | 't' | 'r' | '(' | ')' | '[' | ']' | '-' => 0.36,
+            'm' | 'w' | 'M' | 'W' => 0.85,
+            c if c.is_ascii_uppercase() => 0.68,
+            c if c.is_ascii_digit() => 0.56,
+            c if c.is_ascii() => 0.52,
+            // CJK and other wide scripts
+            _ => 1.0,
+        })
+        .sum::<f32>()
+        * font_size
+}
+
+/// Break `text` at word boundaries into lines fitting the key at `font_size`,
+/// `None` if it needs more than [`MAX_TITLE_LINES`] lines.
+pub fn wrap_title(text: &str, font_size: f32) -> Option<String> {
+    let mut lines: Vec<String> = vec![];
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{line} {word}")
+            };
+            if estimate_width(&candidate, font_size) <= TITLE_WIDTH || line.is_empty() {
+                line = candidate;
+            } else {
+                lines.push(line);
+                line = word.to_string();
+            }
+        }
+        lines.push(line);
+    }
+    let fits = lines.len() <= MAX_TITLE_LINES
+        && lines
+            .iter()
+            .all(|line| estimate_width(line, font_size) <= TITLE_WIDTH);
+    fits.then(|| lines.join("\n"))
+}
+
+/// Wrap `text`, shrinking the font down to [`MIN_TITLE_FONT_SIZE`] until it
+/// fits, and return the wrapped text with the font size to draw it at.
+/// Text too long even at the smallest size is cut with an ellipsis.
+pub fn fit_title(text: &str, font_size: f32) -> (String, f32) {
+    let mut size = font_size;
+    loop {
+        if let Some(wrapped) = wrap_title(text, size) {
+            return (wrapped, size);
+        }
+        if size <= MIN_TITLE_FONT_SIZE {
+            return (truncate_title(text, size), size);
+        }
+        size = (size - 1.0).max(MIN_TITLE_FONT_SIZE);
+    }
+}
+
+// the longest prefix of `text` that wraps in the title, ending with an ellipsis
+fn truncate_title(text: &str, font_size: f32) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let (mut low, mut high) = (0, chars.len());
+    while low < high {
+        let middle = (low + high).div_ceil(2);
+        let candidate: String = chars[..middle].iter().collect();
+        if wrap_title(&format!("{}…", candidate.trim_end()), font_size).is_some() {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    let prefix: String = chars[..low].iter().collect();
+    wrap_title(&format!("{}…", prefix.trim_end()), font_size).unwrap_or_else(|| "…".to_string())
+}
+
+// the part of the looping `text` starting at `offset` chars that fits on one line
+fn marquee_window(text: &[char], offset: usize, font_size: f32) -> String {
+    let mut window = String::new();
+    for i in 0..text.len() {
+        let c = text[(offset + i) % text.len()];
+        window.push(c);
+        if estimate_width(&window, font_size) > TITLE_WIDTH {
+            window.pop();
+            break;
+        }
+    }
+    window
+}
+
+impl StreamDeck {
+    /// Set a title that may not fit on the key: it is wrapped at word
+    /// boundaries for the user's font size and, when longer than
+    /// [`MAX_TITLE_LINES`] lines, handled as `overflow` says.
+    ///
+    /// A marquee runs until the title changes or the context disappears.
+    /// The native title keeps the user's font size; to shrink it instead, draw
+    /// it with `set_key_image_with_fitted_title` (`render` feature).
+    pub async fn set_long_title(&self, context: String, title: &str, overflow: TitleOverflow) {
+        let font_size = self
+            .instances
+            .get(&context)
+            .await
+            .and_then(|instance| instance.title_parameters?.font_size)
+            .map_or(DEFAULT_FONT_SIZE, |size| size as f32);
+        let title = match (wrap_title(title, font_size), overflow) {
+            (Some(wrapped), _) => wrapped,
+            (None, TitleOverflow::Ellipsis) => truncate_title(title, font_size),
+            (None, TitleOverflow::Marquee) => {
+                self.start_marquee(context, title, font_size).await;
+                return;
+            }
+        };
+        self.set_title(context, Some(title)).await;
+    }
+
+    async fn start_marquee(&self, context: String, title: &str, font_size: f32) {
+        self.stop_marquee(&context).await;
+        // the title keeps changing, the next `set_title` must be sent whatever it is
+        self.sent_updates.forget(&context, &Update::Title).await;
+        let text: Vec<char> = title
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .chars()
+            .chain(MARQUEE_GAP.chars())
+            .collect();
+        let sd = self.clone();
+        let task_context = context.clone();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(MARQUEE_INTERVAL);
+            for offset in (0..text.len()).cycle() {
+                interval.tick().await;
+                // started while the key was going away
+                if !sd.is_visible(&task_context).await {
+                    break;
+                }
+                let window = marquee_window(&text, offset, font_size);
+                let key = update_key("setTitle", &task_context, None, None);
+                sd.send_update(
+                    key,
+                    set_title(task_context.clone(), Some(window), None, None),
+                )
+                .await;
+            }
+        });
+        // another update may have started a marquee since `stop_marquee` above
+        if let Some(previous) = self.marquees.lock().await.insert(context, task) {
+            previous.abort();
+        }
+    }
+
+    /// Stop the marquee of `context`, the key keeps the current text.
+    pub async fn stop_marquee(&self, context: &str) {
+        if let Some(task) = self.marquees.lock().await.remove(context) {
+            task.abort();
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use serde_json::json;
+use tokio::sync::Mutex;
+
+use crate::events::events::{AppearEvent, Controller};
+use crate::images::dynamic_image_to_base64;
+use crate::stream_deck::StreamDeck;
+
+/// Size of a single Stream Deck+ touch strip segment (one per encoder).
+pub const SEGMENT_WIDTH: u32 = 200;
+pub const SEGMENT_HEIGHT: u32 = 100;
+/// Number of encoders (and touch strip segments) on a Stream Deck+.
+pub const SEGMENTS: u32 = 4;
+/// Pixmap item of the built-in `$A0` layout covering the whole segment.
+pub const PANORAMA_ITEM: &str = "full-canvas";
+
+#[derive(Clone, Default)]
+pub struct TouchStrip {
+    // (device, action) -> full width image
+    panoramas: Arc<Mutex<HashMap<(String, String), DynamicImage>>>,
+}
+
+impl TouchStrip {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StreamDeck {
+    /// Render one image across the touch strip segments of every visible
+    /// encoder of `action` on `device`.
+    ///
+    /// The image is scaled to 800x100 if needed and split into 200x100 slices
+    /// using each context's `coordinates.column`. Each slice is sent with
+    /// `set_feedback` to the `full-canvas` pixmap of the `$A0` layout, and
+    /// the image is kept to re-render the strip when encoders appear or disappear.
+    pub async fn set_panorama(&self, action: &str, device: &str, image: Vec<u8>) -> bool {
+        let decoded = match image::load_from_memory(&image) {
+            Ok(decoded) => decoded,
+            Err(_) => return false,
+        };
+        let (width, height) = (SEGMENT_WIDTH * SEGMENTS, SEGMENT_HEIGHT);
+        let decoded = if decoded.dimensions() != (width, height) {
+            decoded.resize_exact(width, height, FilterType::Triangle)
+        } else {
+            decoded
+        };
+        self.touch_strip
+            .panoramas
+            .lock()
+            .await
+            .insert((device.to_string(), action.to_string()), decoded);
+        self.render_panorama(action, device).await;
+        true
+    }
+
+    /// Forget the panorama of `action` on `device`, the segments keep their last image.
+    pub async fn clear_panorama(&self, action: &str, device: &str) {
+        self.touch_strip
+            .panoramas
+            .lock()
+            .await
+            .remove(&(device.to_string(), action.to_string()));
+    }
+
+    async fn render_panorama(&self, action: &str, device: &str) {
+        let panoramas = self.touch_strip.panoramas.lock().await;
+        let panorama = match panoramas.get(&(device.to_string(), action.to_string())) {
+            Some(panorama) => panorama.clone(),
+            None => return,
+        };
+        drop(panoramas);
+
+        for key in self.grid.of_device(device).await {
+            if key.action != action || key.controller != Controller::Encoder {
+                continue;
+            }
+            if key.column < 0 || key.column as u32 >= SEGMENTS {
+                continue;
+            }
+            let slice = panorama.crop_imm(
+                key.column as u32 * SEGMENT_WIDTH,
+                0,
+                SEGMENT_WIDTH,
+                SEGMENT_HEIGHT,
+            );
+            if let Some(data) = dynamic_image_to_base64(&slice) {
+                self.set_feedback(key.context, json!({ PANORAMA_ITEM: data }))
+                    .await;
+            }
+        }
+    }
+
+    pub(crate) async fn refresh_touch_strip(&self, e: &AppearEvent) {
+        if e.payload.controller == Some(Controller::Encoder) {
+            self.render_panorama(&e.action, &e.device).await;
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use std::collections::VecDeque;
+
+use crate::render::{Color, KeyImage, Layer, Text};
+use crate::stream_deck::StreamDeck;
+
+/// Range of the values shown by a widget and the colors used to draw them.
+#[derive(Clone, Debug)]
+pub struct Scale {
+    pub min: f64,
+    pub max: f64,
+    /// Color of the values below the first threshold.
+    pub color: Color,
+    /// `(value, color)` pairs, a value takes the color of the highest threshold it reaches.
+    pub thresholds: Vec<(f64, Color)>,
+    /// Color of the unfilled part of bars and gauges.
+    pub track: Color,
+    pub background: Color,
+    /// Decimals of the value label, no label if `None`.
+    pub decimals: Option<usize>,
+    pub unit: String,
+}
+
+impl Scale {
+    pub fn new(min: f64, max: f64) -> Self {
+        Scale {
+            min,
+            max,
+            color: Color::rgb(0x2e, 0xcc, 0x71),
+            thresholds: vec![],
+            track: Color::rgb(0x33, 0x33, 0x33),
+            background: Color::BLACK,
+            decimals: Some(0),
+            unit: String::new(),
+        }
+    }
+
+    pub fn color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn threshold(mut self, value: f64, color: Color) -> Self {
+        self.thresholds.push((value, color));
+        self.thresholds.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self
+    }
+
+    pub fn label(mut self, decimals: Option<usize>, unit: &str) -> Self {
+        self.decimals = decimals;
+        self.unit = unit.to_string();
+        self
+    }
+
+    /// Position of `value` between `min` and `max`, from 0 to 1.
+    pub fn fraction(&self, value: f64) -> f32 {
+        if self.max <= self.min || value.is_nan() {
+            return 0.0;
+        }
+        ((value - self.min) / (self.max - self.min)).clamp(0.0, 1.0) as f32
+    }
+
+    pub fn color_of(&self, value: f64) -> Color {
+        self.thresholds
+            .iter()
+            .rev()
+            .find(|(at, _)| value >= *at)
+            .map_or(self.color, |(_, color)| *color)
+    }
+
+    fn text(&self, value: f64, size: f32) -> Option<Text> {
+        let decimals = self.decimals?;
+        let mut text = Text::new(&format!("{value:.decimals$}{}", self.unit), size);
+        text.wrap = false;
+        Some(text)
+    }
+}
+
+/// Horizontal or vertical bar filled up to the value.
+#[derive(Clone, Debug)]
+pub struct ProgressBar {
+    pub scale: Scale,
+    pub vertical: bool,
+}
+
+impl ProgressBar {
+    pub fn new(scale: Scale) -> Self {
+        ProgressBar {
+            scale,
+            vertical: false,
+        }
+    }
+
+    pub fn vertical(mut self) -> Self {
+        self.vertical = true;
+        self
+    }
+
+    pub fn key_image(&self, value: f64) -> KeyImage {
+        let fraction = self.scale.fraction(value);
+        let color = self.scale.color_of(value);
+        let mut image = KeyImage::new().background(self.scale.background);
+        if self.vertical {
+            let (x, y, width, height) = (44.0, 8.0, 16.0, 56.0);
+            let filled = height * fraction;
+            image = image
+                .layer(rounded(x, y, width, height, self.scale.track))
+                .layer(rounded(x, y + height - filled, width, filled, color));
+            if let Some(text) = self.scale.text(value, 16.0) {
+                image = image.text(text.within(0.0, 0.0, 44.0, 72.0));
+            }
+        } else {
+            let (x, y, width, height) = (8.0, 46.0, 56.0, 14.0);
+            image = image
+                .layer(rounded(x, y, width, height, self.scale.track))
+                .layer(rounded(x, y, width * fraction, height, color));
+            if let Some(text) = self.scale.text(value, 20.0) {
+                image = image.text(text.within(0.0, 8.0, 72.0, 34.0));
+            }
+        }
+        image
+    }
+}
+
+fn rounded(x: f32, y: f32, width: f32, height: f32, color: Color) -> Layer {
+    Layer::Rect {
+        x,
+        y,
+        width,
+        height,
+        radius: width.min(height) / 2.0,
+        color,
+    }
+}
+
+/// Circular gauge, a 270° arc open at the bottom with the value in the middle.
+#[derive(Clone, Debug)]
+pub struct Gauge {
+    pub scale: Scale,
+}
+
+impl Gauge {
+    pub fn new(scale: Scale) -> Self {
+        Gauge { scale }
+    }
+
+    pub fn key_image(&self, value: f64) -> KeyImage {
+        const START: f32 = -135.0;
+        const SWEEP: f32 = 270.0;
+        let arc = |sweep: f32, color: Color| Layer::Arc {
+            x: 36.0,
+            y: 38.0,
+            radius: 27.0,
+            width: 7.0,
+            start: START,
+            sweep,
+            color,
+        };
+        let mut image = KeyImage::new()
+            .background(self.scale.background)
+            .layer(arc(SWEEP, self.scale.track));
+        let fraction = self.scale.fraction(value);
+        if fraction > 0.0 {
+            image = image.layer(arc(SWEEP * fraction, self.scale.color_of(value)));
+        }
+        if let Some(text) = self.scale.text(value, 16.0) {
+            image = image.text(text.within(14.0, 24.0, 44.0, 28.0));
+        }
+        image
+    }
+}
+
+/// Rolling buffer keeping the last `capacity` samples of a series.
+#[derive(Clone, Debug)]
+pub struct Samples {
+    values: VecDeque<f64>,
+    capacity: usize,
+}
+
+impl Samples {
+    pub fn new(capacity: usize) -> Self {
+        Samples {
+            values: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// Add a sample, dropping the oldest one when full.
+    pub fn push(&mut self, value: f64) {
+        if self.values.len() == self.capacity {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    pub fn last(&self) -> Option<f64> {
+        self.values.back().copied()
+    }
+
+    /// Lowest and highest samples, ignoring NaN.
+    pub fn bounds(&self) -> Option<(f64, f64)> {
+        self.values
+            .iter()
+            .filter(|value| !value.is_nan())
+            .fold(None, |bounds, &value| match bounds {
+                None => Some((value, value)),
+                Some((min, max)) => Some((value.min(min), value.max(max))),
+            })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = f64> + '_ {
+        self.values.iter().copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+/// Line chart of the samples, with the last one as label.
+#[derive(Clone, Debug)]
+pub struct Sparkline {
+    pub scale: Scale,
+    /// Fit the chart to the lowest and highest samples instead of `min` and `max`.
+    pub autoscale: bool,
+}
+
+impl Sparkline {
+    pub fn new(scale: Scale) -> Self {
+        Sparkline {
+            scale,
+            autoscale: false,
+        }
+    }
+
+    pub fn autoscale(mut self) -> Self {
+        self.autoscale = true;
+        self
+    }
+
+    pub fn key_image(&self, samples: &Samples) -> KeyImage {
+        const LEFT: f32 = 6.0;
+        const TOP: f32 = 30.0;
+        const WIDTH: f32 = 60.0;
+        const HEIGHT: f32 = 34.0;
+        let mut image = KeyImage::new().background(self.scale.background);
+        let last = match samples.last() {
+            Some(last) => last,
+            None => return image,
+        };
+        let mut scale = self.scale.clone();
+        if self.autoscale {
+            if let Some((min, max)) = samples.bounds() {
+                scale.min = min;
+                scale.max = max;
+            }
+        }
+        // the x axis always spans the full buffer so the chart scrolls as it fills
+        let step = WIDTH / (samples.capacity().max(2) - 1) as f32;
+        let offset = (samples.capacity() - samples.len()) as f32 * step;
+        let points = samples
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let fraction = if scale.max > scale.min {
+                    scale.fraction(value)
+                } else {
+                    0.5
+                };
+                (
+                    LEFT + offset + i as f32 * step,
+                    TOP + HEIGHT * (1.0 - fraction),
+                )
+            })
+            .collect();
+        image = image.layer(Layer::Polyline {
+            points,
+            width: 2.5,
+            color: self.scale.color_of(last),
+        });
+        if let Some(text) = self.scale.text(last, 16.0) {
+            image = image.text(text.within(0.0, 2.0, 72.0, 26.0));
+        }
+        image
+    }
+}
+
+impl StreamDeck {
+    pub async fn set_progress(&self, context: String, bar: &ProgressBar, value: f64) {
+        self.set_key_image(context, &bar.key_image(value)).await;
+    }
+
+    pub async fn set_gauge(&self, context: String, gauge: &Gauge, value: f64) {
+        self.set_key_image(context, &gauge.key_image(value)).await;
+    }
+
+    pub async fn set_sparkline(&self, context: String, sparkline: &Sparkline, samples: &Samples) {
+        self.set_key_image(context, &sparkline.key_image(samples))
+            .await;
+    }
+}